@@ -0,0 +1,178 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Shown in place of what was cut from a tab name.
+const ELLIPSIS: &str = "…";
+
+/// How a path is shortened when the tab name exceeds `max_tab_name_length`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AbbreviationStrategy {
+    /// Shorten the leading components to their first character, one at a time:
+    /// `git-project/src/components`, `g/src/components`, `g/s/components`.
+    Fish,
+    /// Cut the middle of the name: `git-pro…components`.
+    Middle,
+    /// Keep only the last `N` components: `…/src/components`.
+    LastComponents(usize),
+}
+
+/// The number of terminal columns `value` takes up.
+pub fn width(value: &str) -> usize {
+    value.graphemes(true).map(grapheme_width).sum()
+}
+
+/// The columns a grapheme cluster takes up. Clusters like `👩‍💻` are made up of several wide
+/// characters but are still shown in two columns.
+fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().min(2)
+}
+
+/// The longest start of `value` that fits into `max_width` columns, without splitting
+/// grapheme clusters.
+fn prefix(value: &str, max_width: usize) -> &str {
+    let mut width = 0;
+
+    for (index, grapheme) in value.grapheme_indices(true) {
+        width += grapheme_width(grapheme);
+        if width > max_width {
+            return &value[..index];
+        }
+    }
+
+    value
+}
+
+/// The longest end of `value` that fits into `max_width` columns, without splitting
+/// grapheme clusters.
+fn suffix(value: &str, max_width: usize) -> &str {
+    let mut width = 0;
+
+    for (index, grapheme) in value.grapheme_indices(true).rev() {
+        width += grapheme_width(grapheme);
+        if width > max_width {
+            return &value[index + grapheme.len()..];
+        }
+    }
+
+    value
+}
+
+/// The shortened forms of `path` the strategy goes through, from the least to the most
+/// abbreviated. `Middle` works on the whole name instead and yields nothing.
+pub fn abbreviate_path(path: &str, strategy: AbbreviationStrategy) -> Vec<String> {
+    let components: Vec<&str> = path.split('/').collect();
+
+    match strategy {
+        AbbreviationStrategy::Fish => (1..components.len())
+            .map(|abbreviated| {
+                components
+                    .iter()
+                    .enumerate()
+                    .map(|(index, component)| {
+                        if index < abbreviated {
+                            abbreviate_component(component)
+                        } else {
+                            (*component).to_string()
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join("/")
+            })
+            .collect(),
+        AbbreviationStrategy::LastComponents(count) if components.len() > count => {
+            let kept = &components[components.len() - count..];
+            vec![format!("{ELLIPSIS}/{}", kept.join("/"))]
+        }
+        AbbreviationStrategy::Middle | AbbreviationStrategy::LastComponents(_) => Vec::new(),
+    }
+}
+
+/// The first character of a path component, keeping a leading `.` of hidden directories.
+fn abbreviate_component(component: &str) -> String {
+    let mut graphemes = component.graphemes(true);
+
+    match graphemes.next() {
+        Some(".") => graphemes
+            .next()
+            .map_or_else(|| String::from("."), |grapheme| format!(".{grapheme}")),
+        Some(grapheme) => grapheme.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Cuts `value` down to `max_width` columns, replacing the end with an ellipsis.
+pub fn truncate_end(value: &str, max_width: usize) -> String {
+    if width(value) <= max_width {
+        return value.to_string();
+    }
+
+    let kept = max_width.saturating_sub(width(ELLIPSIS));
+    format!("{}{ELLIPSIS}", prefix(value, kept))
+}
+
+/// Cuts `value` down to `max_width` columns, replacing the middle with an ellipsis.
+pub fn truncate_middle(value: &str, max_width: usize) -> String {
+    if width(value) <= max_width {
+        return value.to_string();
+    }
+
+    let kept = max_width.saturating_sub(width(ELLIPSIS));
+    let head = prefix(value, kept - kept / 2);
+    let tail = suffix(value, kept - width(head));
+
+    format!("{head}{ELLIPSIS}{tail}")
+}
+
+/// Shows the first `preview_width` columns of `value`, followed by `...` if anything was cut.
+/// `0` shows all of it.
+pub fn preview(value: &str, preview_width: usize) -> String {
+    if preview_width == 0 || width(value) <= preview_width {
+        return value.to_string();
+    }
+
+    format!("{}...", prefix(value, preview_width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abbreviates_paths() {
+        assert_eq!(
+            abbreviate_path("git-project/.config/src", AbbreviationStrategy::Fish),
+            vec!["g/.config/src", "g/.c/src"]
+        );
+        assert_eq!(
+            abbreviate_path("~/a/b/c", AbbreviationStrategy::LastComponents(2)),
+            vec!["…/b/c"]
+        );
+        assert!(abbreviate_path("b/c", AbbreviationStrategy::LastComponents(2)).is_empty());
+    }
+
+    #[test]
+    fn measures_display_width() {
+        assert_eq!(width("src"), 3);
+        assert_eq!(width("cafe\u{301}"), 4);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("👩‍💻/src"), 6);
+    }
+
+    #[test]
+    fn truncates_at_grapheme_boundaries() {
+        assert_eq!(truncate_end("café-project", 5), "café…");
+        assert_eq!(truncate_end("cafe\u{301}", 4), "cafe\u{301}");
+        assert_eq!(truncate_end("e\u{301}e\u{301}e\u{301}", 2), "e\u{301}…");
+        assert_eq!(truncate_middle("abcdefghij", 6), "abc…ij");
+    }
+
+    #[test]
+    fn truncates_wide_characters_by_width() {
+        assert_eq!(truncate_end("~/プロジェクト/src", 8), "~/プロ…");
+        assert_eq!(truncate_end("~/プロジェクト/src", 7), "~/プロ…");
+        assert_eq!(truncate_middle("日本語/src/中文", 9), "日本…中文");
+        assert_eq!(truncate_end("👩‍💻👩‍💻", 3), "👩‍💻…");
+        assert_eq!(preview("機能ブランチ", 5), "機能...");
+        assert_eq!(preview("feature-branch", 0), "feature-branch");
+    }
+}
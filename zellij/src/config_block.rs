@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+
+use kdl::{KdlDocument, KdlNode};
+
+/// Parses the child block `key` of the plugin configuration, like `path_rules { ... }`, with
+/// one item per node. Nodes `parse_node` rejects are logged as `what` and skipped. `None` if the
+/// block isn't configured.
+pub fn parse_block<T>(
+    configuration: &BTreeMap<String, String>,
+    key: &str,
+    what: &str,
+    parse_node: impl Fn(&KdlNode) -> Result<T, String>,
+) -> Option<Vec<T>> {
+    let block = configuration.get(key)?;
+
+    let document = match block.parse::<KdlDocument>() {
+        Ok(document) => document,
+        Err(err) => {
+            eprintln!("Failed to parse {key}: {err}");
+            return Some(Vec::new());
+        }
+    };
+
+    Some(
+        document
+            .nodes()
+            .iter()
+            .filter_map(|node| match parse_node(node) {
+                Ok(item) => Some(item),
+                Err(err) => {
+                    eprintln!("Ignoring {what} {node}: {err}");
+                    None
+                }
+            })
+            .collect(),
+    )
+}
+
+/// The positional string arguments of a node, e.g. `"~/work" "W:"`.
+pub fn string_arguments(node: &KdlNode) -> Vec<&str> {
+    node.entries()
+        .iter()
+        .filter(|entry| entry.name().is_none())
+        .filter_map(|entry| entry.value().as_string())
+        .collect()
+}
@@ -1,14 +1,264 @@
+mod abbreviation;
+mod config_block;
+mod path_cache;
+mod path_prefixes;
+mod path_rules;
+mod persistence;
+mod pipe_command;
+mod project;
+mod status;
+mod template;
+
 use zellij_tile::prelude::*;
 
+use abbreviation::AbbreviationStrategy;
+use path_cache::PathCache;
+use path_prefixes::PathPrefixes;
+use path_rules::PathRules;
+use persistence::{
+    PersistedLock, PersistedPaneStatus, PersistedState, PersistedTabTemplate,
+    DEAD_PROCESSES_SCRIPT, STATE_DIR_MOUNT,
+};
+use pipe_command::{parse_pipe_command, PipeCommand};
+use project::{
+    parse_package_output, Package, ProjectDetectors, PACKAGE_SCRIPT, PROJECT_ROOT_SCRIPT,
+};
+use status::{PaneStatus, StatusDefinition, StatusRegistry};
+use template::{Placeholder, Template};
+
+use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::path::Path;
-use std::{collections::BTreeMap, path::PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    path::PathBuf,
+};
 
 #[derive(Clone, Debug)]
 struct PathMetadata {
     git_worktree_root: PathBuf,
     repo_name: String,
     worktree_name: String,
+    head: GitHead,
+    /// `None` until `git status` has been run for the path
+    status: Option<GitStatus>,
+    /// The workspace member the path is in, `None` until it has been looked up or outside of one
+    package: Option<Package>,
+    /// The root of the superproject, if the git worktree is a submodule
+    superproject_root: Option<PathBuf>,
+}
+
+/// The state of `HEAD` in a git worktree.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct GitHead {
+    /// The checked out branch, `None` if `HEAD` is detached
+    branch: Option<String>,
+    /// The abbreviated commit `HEAD` points to, `None` if there are no commits yet
+    commit: Option<String>,
+    /// The operation currently in progress, e.g. a rebase
+    operation: Option<GitOperation>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GitOperation {
+    Rebase,
+    Merge,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+impl GitOperation {
+    fn name(self) -> &'static str {
+        match self {
+            GitOperation::Rebase => "rebase",
+            GitOperation::Merge => "merge",
+            GitOperation::CherryPick => "cherry-pick",
+            GitOperation::Revert => "revert",
+            GitOperation::Bisect => "bisect",
+        }
+    }
+}
+
+/// A summary of `git status` for a git worktree.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct GitStatus {
+    /// Number of files with staged changes
+    staged: usize,
+    /// Number of files with unstaged changes
+    modified: usize,
+    /// Number of untracked files
+    untracked: usize,
+    /// Number of files with merge conflicts
+    conflicted: usize,
+    /// Number of commits the branch is ahead of its upstream
+    ahead: usize,
+    /// Number of commits the branch is behind its upstream
+    behind: usize,
+    /// Number of stash entries
+    stashes: usize,
+}
+
+impl GitStatus {
+    /// Renders the status as compact glyphs, e.g. `+!?⇡2`. Clean worktrees render as nothing.
+    fn glyphs(&self) -> String {
+        let mut glyphs = String::new();
+
+        if self.conflicted > 0 {
+            glyphs.push('=');
+        }
+        if self.staged > 0 {
+            glyphs.push('+');
+        }
+        if self.modified > 0 {
+            glyphs.push('!');
+        }
+        if self.untracked > 0 {
+            glyphs.push('?');
+        }
+        for (count, glyph) in [(self.ahead, '⇡'), (self.behind, '⇣'), (self.stashes, '$')] {
+            if count > 0 {
+                glyphs.push(glyph);
+                glyphs.push_str(&count.to_string());
+            }
+        }
+
+        glyphs
+    }
+}
+
+fn parse_git_status_output(stdout: &str) -> GitStatus {
+    let mut status = GitStatus::default();
+
+    for line in stdout.lines() {
+        if let Some(ahead_behind) = line.strip_prefix("# branch.ab ") {
+            for count in ahead_behind.split(' ') {
+                if let Some(ahead) = count.strip_prefix('+') {
+                    status.ahead = ahead.parse().unwrap_or_default();
+                } else if let Some(behind) = count.strip_prefix('-') {
+                    status.behind = behind.parse().unwrap_or_default();
+                }
+            }
+        } else if let Some(stashes) = line.strip_prefix("# stash ") {
+            status.stashes = stashes.parse().unwrap_or_default();
+        } else if line.starts_with("1 ") || line.starts_with("2 ") {
+            let mut xy = line[2..].chars();
+            if xy.next().is_some_and(|x| x != '.') {
+                status.staged += 1;
+            }
+            if xy.next().is_some_and(|y| y != '.') {
+                status.modified += 1;
+            }
+        } else if line.starts_with("u ") {
+            status.conflicted += 1;
+        } else if line.starts_with("? ") {
+            status.untracked += 1;
+        }
+    }
+
+    status
+}
+
+/// Prints `branch <name>`, `commit <hash>` and `operation <name>` lines for the git worktree
+/// in the current directory. Each line is omitted if it does not apply.
+const GIT_HEAD_STATE_SCRIPT: &str = r#"
+if branch=$(git symbolic-ref --short -q HEAD); then echo "branch $branch"; fi
+if commit=$(git rev-parse --short -q --verify HEAD); then echo "commit $commit"; fi
+for entry in rebase-merge:rebase rebase-apply:rebase MERGE_HEAD:merge \
+    CHERRY_PICK_HEAD:cherry-pick REVERT_HEAD:revert BISECT_LOG:bisect; do
+    if [ -e "$(git rev-parse --path-format=absolute --git-path "${entry%%:*}")" ]; then
+        echo "operation ${entry#*:}"
+        break
+    fi
+done
+exit 0
+"#;
+
+fn parse_git_head_state_output(stdout: &str) -> GitHead {
+    let mut head = GitHead::default();
+
+    for line in stdout.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+
+        match key {
+            "branch" => head.branch = Some(value.to_string()),
+            "commit" => head.commit = Some(value.to_string()),
+            "operation" => {
+                head.operation = match value {
+                    "rebase" => Some(GitOperation::Rebase),
+                    "merge" => Some(GitOperation::Merge),
+                    "cherry-pick" => Some(GitOperation::CherryPick),
+                    "revert" => Some(GitOperation::Revert),
+                    "bisect" => Some(GitOperation::Bisect),
+                    _ => None,
+                };
+            }
+            _ => eprintln!("Unexpected git head state line: {line}"),
+        }
+    }
+
+    head
+}
+
+/// Prints the value of each environment variable given as an argument, followed by a NUL.
+const ENVIRONMENT_SCRIPT: &str = r#"for name; do printenv "$name"; printf '\0'; done"#;
+
+/// The non-empty values of `variables`, in the order they were printed by `ENVIRONMENT_SCRIPT`.
+fn parse_environment_output(variables: &[&str], stdout: &str) -> BTreeMap<String, String> {
+    variables
+        .iter()
+        .zip(stdout.split('\0'))
+        .map(|(variable, value)| (variable, value.strip_suffix('\n').unwrap_or(value)))
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(variable, value)| ((*variable).to_string(), value.to_string()))
+        .collect()
+}
+
+/// The root of the project containing `path` printed by `PROJECT_ROOT_SCRIPT`, described like a
+/// git worktree named after the root directory.
+fn parse_project_root_output(path: &Path, stdout: &str) -> Option<PathMetadata> {
+    let root = PathBuf::from(stdout.lines().next()?.trim_end_matches('/'));
+
+    if !root.is_absolute() || !path.starts_with(&root) {
+        eprintln!(
+            "Unexpected project root {} for {}",
+            root.display(),
+            path.display()
+        );
+        return None;
+    }
+
+    let name = root.file_name()?.to_str()?.to_string();
+
+    Some(PathMetadata {
+        git_worktree_root: root,
+        repo_name: name.clone(),
+        worktree_name: name,
+        head: GitHead::default(),
+        status: None,
+        package: None,
+        superproject_root: None,
+    })
+}
+
+/// What is known about a path, cached in `State::path_metadata`.
+#[derive(Clone, Debug)]
+enum PathKind {
+    Git(PathMetadata),
+    /// The path is inside a project found by `project_detectors`, without git `HEAD` or status
+    Project(PathMetadata),
+    /// The path is not inside a git worktree or other project
+    NotGit,
+}
+
+enum SubmoduleDisplay {
+    /// Show the path inside the submodule as if it was a repository of its own.
+    SubmoduleOnly,
+    /// Prefix the submodule name with the superproject name: `parent⟩sub/src`.
+    SuperprojectAndSubmodule,
 }
 
 enum WorktreeNameDisplay {
@@ -18,81 +268,374 @@ enum WorktreeNameDisplay {
     WorktreeOnly,
 }
 
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-enum PaneStatus {
-    #[default]
-    None,
-    Waiting,
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ManualRenamePolicy {
+    /// Stop renaming a tab once it was renamed by the user.
+    Lock,
+    /// Keep renaming tabs, overriding the user's names.
+    Override,
 }
 
-fn format_path(state: &State, path: &Path, path_suffix: &str) -> String {
-    let git_metadata = state.get_git_path_metadata(path.to_path_buf());
+/// The name tabula last gave a tab, to tell it apart from names the user gave it.
+#[derive(Debug, Default)]
+struct AppliedTabName {
+    name: String,
+    /// Names the tab may still show until the rename to `name` has been reported
+    previous_names: BTreeSet<String>,
+}
+
+/// Everything known about a tab that can be used to render its name.
+struct TabNameContext {
+    /// The glyph of the highest priority status of the panes in the tab
+    status_glyph: String,
+    /// The description of `dir`
+    path: PathDescription,
+    /// The directory the tab is named after
+    dir: PathBuf,
+    /// The number of terminal panes in the tab
+    pane_count: usize,
+    /// The command running in the focused or only pane, unless it's a shell
+    command: Option<String>,
+    /// How the name is built from `path`
+    summary: TabSummary,
+}
+
+/// How the name of a tab is built, which depends on the `multi_pane_strategy` if its panes are
+/// in different directories.
+enum TabSummary {
+    /// The path, followed by `path_suffix` and an annotation like ` (2 panes)` or ` +1`
+    Path {
+        path_suffix: &'static str,
+        annotation: String,
+    },
+    /// Text replacing the path, e.g. `api+web`
+    Text(String),
+}
+
+impl TabSummary {
+    fn path(path_suffix: &'static str) -> TabSummary {
+        TabSummary::Path {
+            path_suffix,
+            annotation: String::new(),
+        }
+    }
+}
 
-    let result = format!("{}", path.display());
+/// How a tab with panes in different directories is named.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MultiPaneStrategy {
+    /// The closest common ancestor, e.g. `repo/* (2 panes)`
+    CommonAncestor,
+    /// The directory of the most recently focused pane
+    LastFocused,
+    /// The directory most panes are in
+    Majority,
+    /// The repository names of all directories, e.g. `api+web`
+    Repos,
+    /// The directory of the most recently focused pane and the number of other directories,
+    /// e.g. `api/src +1`
+    FocusedPlusCount,
+}
+
+impl MultiPaneStrategy {
+    /// Whether tab names change when the focus moves to another pane.
+    fn follows_focus(self) -> bool {
+        matches!(
+            self,
+            MultiPaneStrategy::LastFocused | MultiPaneStrategy::FocusedPlusCount
+        )
+    }
+}
+
+impl TabNameContext {
+    /// The name tabula generates without a custom template.
+    fn name(&self) -> String {
+        match &self.summary {
+            TabSummary::Path {
+                path_suffix,
+                annotation,
+            } => format!(
+                "{}{annotation}",
+                format_path_description(&self.path, path_suffix)
+            ),
+            TabSummary::Text(text) => text.clone(),
+        }
+    }
+
+    fn value(&self, placeholder: Placeholder) -> String {
+        match placeholder {
+            Placeholder::Status => self.status_glyph.clone(),
+            Placeholder::Name => self.name(),
+            Placeholder::Path => self.path.display.clone(),
+            Placeholder::AbsPath => self.dir.display().to_string(),
+            Placeholder::Dir => self
+                .dir
+                .file_name()
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Placeholder::Repo => self.path.repo.clone().unwrap_or_default(),
+            Placeholder::RelPath => self
+                .path
+                .rel_path
+                .as_ref()
+                .map(|rel_path| rel_path.display().to_string())
+                .unwrap_or_default(),
+            Placeholder::Worktree => self.path.worktree.clone().unwrap_or_default(),
+            Placeholder::WorktreeRoot => self
+                .path
+                .worktree_root
+                .as_ref()
+                .map(|root| root.display().to_string())
+                .unwrap_or_default(),
+            Placeholder::Branch => self.path.head.branch.clone().unwrap_or_default(),
+            Placeholder::Commit => match self.path.head.branch {
+                Some(_) => String::new(),
+                None => self.path.head.commit.clone().unwrap_or_default(),
+            },
+            Placeholder::Head => self
+                .path
+                .head
+                .branch
+                .clone()
+                .or_else(|| self.path.head.commit.clone())
+                .unwrap_or_default(),
+            Placeholder::Operation => self
+                .path
+                .head
+                .operation
+                .map(|operation| operation.name().to_string())
+                .unwrap_or_default(),
+            Placeholder::GitStatus => self
+                .path
+                .status
+                .as_ref()
+                .map(GitStatus::glyphs)
+                .unwrap_or_default(),
+            Placeholder::Superproject => self.path.superproject.clone().unwrap_or_default(),
+            Placeholder::Package => self.path.package.clone().unwrap_or_default(),
+            Placeholder::PackagePath => self
+                .path
+                .package_path
+                .as_ref()
+                .map(|package_path| package_path.display().to_string())
+                .unwrap_or_default(),
+            // `/*` only if the tab is named after the common ancestor of several directories
+            Placeholder::MultiSuffix => match &self.summary {
+                TabSummary::Path { path_suffix, .. } => (*path_suffix).to_string(),
+                TabSummary::Text(_) => String::from("/*"),
+            },
+            Placeholder::PaneCount => self.pane_count.to_string(),
+            Placeholder::Command => self.command.clone().unwrap_or_default(),
+            // Filled in by `State::tab_name` for templates given as tab names
+            Placeholder::Auto => String::new(),
+        }
+    }
+}
+
+/// The pieces of a path that tab names are built from.
+#[derive(Clone, Debug, Default)]
+struct PathDescription {
+    /// The shortened path, e.g. `repo/src` or `~/project/src`.
+    display: String,
+    /// The git repository name, if the path is inside a git worktree.
+    repo: Option<String>,
+    /// The path relative to the git worktree root.
+    rel_path: Option<PathBuf>,
+    /// The (possibly truncated) name of the linked worktree, if any.
+    worktree: Option<String>,
+    /// The root of the git worktree.
+    worktree_root: Option<PathBuf>,
+    /// Whether the worktree name should be appended as `(🌲 worktree)`.
+    show_worktree_annotation: bool,
+    /// The state of `HEAD` in the git worktree.
+    head: GitHead,
+    /// The `git status` summary of the git worktree, if known.
+    status: Option<GitStatus>,
+    /// The name of the superproject, if the git worktree is a submodule.
+    superproject: Option<String>,
+    /// The name of the workspace member the path is in, e.g. a crate of a cargo workspace.
+    package: Option<String>,
+    /// The path relative to the root of the workspace member.
+    package_path: Option<PathBuf>,
+}
+
+fn describe_path(state: &State, path: &Path) -> PathDescription {
+    let mut description = describe_git_or_home_path(state, path);
+
+    if let Some(display) = state.path_rules.apply(path, state.home_dir()) {
+        description.display = display;
+    }
+
+    description
+}
+
+fn describe_git_or_home_path(state: &State, path: &Path) -> PathDescription {
+    let git_metadata = state.get_git_path_metadata(path.to_path_buf());
 
     if let Some(git_metadata) = git_metadata {
         if let Ok(relative_path) = path.strip_prefix(&git_metadata.git_worktree_root) {
             let is_linked_worktree = git_metadata.worktree_name != git_metadata.repo_name;
 
+            let mut description = PathDescription {
+                display: String::new(),
+                repo: Some(git_metadata.repo_name.clone()),
+                rel_path: Some(relative_path.to_path_buf()),
+                worktree: None,
+                worktree_root: Some(git_metadata.git_worktree_root.clone()),
+                show_worktree_annotation: false,
+                head: git_metadata.head.clone(),
+                status: git_metadata.status.clone(),
+                superproject: git_metadata
+                    .superproject_root
+                    .as_ref()
+                    .and_then(|root| root.file_name())
+                    .map(|name| name.to_string_lossy().into_owned()),
+                package: None,
+                package_path: None,
+            };
+
+            if let Some(package) = &git_metadata.package {
+                if let Ok(package_path) = path.strip_prefix(&package.root) {
+                    description.package = Some(package.name.clone());
+                    description.package_path = Some(package_path.to_path_buf());
+                }
+            }
+
+            let mut path_root = git_metadata.repo_name.clone();
+
             if is_linked_worktree {
+                description.worktree = Some(abbreviation::preview(
+                    &git_metadata.worktree_name,
+                    state.worktree_name_preview_length(),
+                ));
+
                 match state.worktree_name_display() {
                     WorktreeNameDisplay::RepoAndWorktree => {
-                        let worktree_name = truncate_with_ellipsis(
-                            &git_metadata.worktree_name,
-                            state.worktree_name_preview_length(),
-                        );
-                        let path = if relative_path.as_os_str().is_empty() {
-                            git_metadata.repo_name.clone()
-                        } else {
-                            format!("{}/{}", git_metadata.repo_name, relative_path.display())
-                        };
-
-                        return format!("{path}{path_suffix} (🌲 {worktree_name})");
+                        description.show_worktree_annotation = true;
                     }
                     WorktreeNameDisplay::WorktreeOnly => {
-                        let path = if relative_path.as_os_str().is_empty() {
-                            git_metadata.worktree_name.clone()
-                        } else {
-                            format!("{}/{}", git_metadata.worktree_name, relative_path.display())
-                        };
-
-                        return format!("{path}{path_suffix}");
+                        path_root.clone_from(&git_metadata.worktree_name);
                     }
                 }
             }
 
-            let path = if relative_path.as_os_str().is_empty() {
-                git_metadata.repo_name.clone()
+            if let (Some(superproject), SubmoduleDisplay::SuperprojectAndSubmodule) =
+                (&description.superproject, state.submodule_display())
+            {
+                path_root = format!("{superproject}⟩{path_root}");
+            }
+
+            description.display = if relative_path.as_os_str().is_empty() {
+                path_root
             } else {
-                format!("{}/{}", git_metadata.repo_name, relative_path.display())
+                format!("{}/{}", path_root, relative_path.display())
             };
 
-            return format!("{path}{path_suffix}");
+            return description;
         }
     }
 
-    if let Some(home_dir) = state.userspace_configuration.get("home_dir") {
-        let home_dir = home_dir.trim_end_matches('/');
-        if path.starts_with(home_dir) {
-            return format!("~{}{}", result.trim_start_matches(home_dir), path_suffix);
+    let display = state
+        .path_prefixes
+        .apply(path, state.home_dir(), &state.environment)
+        .unwrap_or_else(|| format!("{}", path.display()));
+
+    PathDescription {
+        display,
+        ..PathDescription::default()
+    }
+}
+
+/// The closest directory that contains all of `dirs`.
+fn common_ancestor(dirs: &[&PathBuf]) -> PathBuf {
+    let Some(first_dir) = dirs.first() else {
+        return PathBuf::new();
+    };
+
+    let mut common_dir = (*first_dir).clone();
+
+    for dir in dirs {
+        while !dir.starts_with(&common_dir) {
+            if let Some(parent) = common_dir.parent() {
+                common_dir = parent.to_path_buf();
+            } else {
+                break;
+            }
         }
     }
 
-    format!("{result}{path_suffix}")
+    common_dir
 }
 
-fn truncate_with_ellipsis(value: &str, preview_length: usize) -> String {
-    if preview_length == 0 {
-        return value.to_string();
+/// Shells, which aren't worth showing as the command of a pane.
+const DEFAULT_IGNORED_COMMANDS: [&str; 13] = [
+    "bash", "zsh", "fish", "sh", "dash", "ksh", "csh", "tcsh", "nu", "xonsh", "elvish", "pwsh",
+    "login",
+];
+
+/// The command running in a pane, e.g. `cargo test`, taken from the command of a command pane
+/// or from the title the program in the pane set.
+fn pane_command(pane: &PaneInfo, ignored_commands: &[String]) -> Option<String> {
+    let command = if let Some(command) = &pane.terminal_command {
+        command.trim()
+    } else {
+        let title = pane.title.trim();
+
+        // Shells often set the title to the working directory, possibly after `user@host:`, and
+        // zellij's default is `Pane #1`
+        let first_word = title.split_whitespace().next().unwrap_or_default();
+        if title.starts_with(['/', '~'])
+            || title.starts_with("Pane #")
+            || first_word.ends_with(':')
+            || first_word.contains('@') && first_word.contains(':')
+        {
+            return None;
+        }
+
+        title
+    };
+
+    let mut words = command.split_whitespace();
+    let program = words.next()?;
+    // The name of the executable, without its directory or the `-` of a login shell
+    let program = program
+        .rsplit('/')
+        .next()
+        .unwrap_or(program)
+        .trim_start_matches('-');
+
+    if program.is_empty() || ignored_commands.iter().any(|ignored| ignored == program) {
+        return None;
     }
 
-    let truncated_value: String = value.chars().take(preview_length).collect();
+    Some(
+        std::iter::once(program)
+            .chain(words)
+            .collect::<Vec<&str>>()
+            .join(" "),
+    )
+}
+
+fn format_path_description(description: &PathDescription, path_suffix: &str) -> String {
+    let path = &description.display;
+
+    match &description.worktree {
+        Some(worktree_name) if description.show_worktree_annotation => {
+            format!("{path}{path_suffix} (🌲 {worktree_name})")
+        }
+        _ => format!("{path}{path_suffix}"),
+    }
+}
 
-    if truncated_value.chars().count() == value.chars().count() {
-        return truncated_value;
+/// Parses a tab name as a template if it references any placeholder, e.g. `api: {auto}`.
+fn parse_tab_template(name: &str) -> Option<Template> {
+    if !name.contains('{') {
+        return None;
     }
 
-    format!("{truncated_value}...")
+    Template::parse(name)
+        .ok()
+        .filter(Template::has_placeholders)
 }
 
 #[derive(Default)]
@@ -112,37 +655,121 @@ struct State {
     /// Maps pane id to its current status.
     pane_statuses: BTreeMap<u32, PaneStatus>,
 
+    /// The focused terminal pane and since when it has been focused
+    focused_pane: Option<(u32, Instant)>,
+
+    /// When each terminal pane was last focused within its tab
+    pane_focused_at: BTreeMap<u32, Instant>,
+
+    /// `pane_focused_at` as tab names are rendered with, which lags behind while a focus rename
+    /// is debounced
+    named_focus_at: BTreeMap<u32, Instant>,
+
+    /// The tabs tabula doesn't rename, with the name the user gave them, by tab id. tabula only
+    /// adds the status glyph to it.
+    locked_tabs: BTreeMap<usize, String>,
+
+    /// Templates given as tab names, e.g. `api: {auto}`, by tab id
+    tab_templates: BTreeMap<usize, Template>,
+
+    /// The names tabula last gave tabs, by tab id
+    applied_tab_names: RefCell<BTreeMap<usize, AppliedTabName>>,
+
+    /// The statuses panes can be set to, built from the configuration
+    statuses: StatusRegistry,
+
     /// Whether the plugin has the necessary permissions
     permissions: Option<PermissionStatus>,
 
     /// Metadata about paths
-    path_metadata: BTreeMap<PathBuf, PathMetadata>,
-}
+    path_metadata: PathCache<PathKind>,
 
-register_plugin!(State);
+    /// Commands that have been started but whose result hasn't arrived yet, by fn and path
+    pending_commands: RefCell<BTreeMap<(String, PathBuf), PendingCommand>>,
+
+    /// The template used to render tab names, parsed from `tab_name_template`
+    tab_name_template: Template,
 
-fn rem_first_and_last(value: &str) -> &str {
-    let mut chars = value.chars();
-    chars.next();
-    chars.next_back();
-    chars.as_str()
+    /// Display names for directories, parsed from `path_rules`
+    path_rules: PathRules,
+
+    /// Shortened names for the beginnings of paths, parsed from `path_prefixes`
+    path_prefixes: PathPrefixes,
+
+    /// How projects outside of git are recognized, parsed from `project_detectors`
+    project_detectors: ProjectDetectors,
+
+    /// When cached path metadata is next checked for expiry, `None` if the refresh is disabled
+    next_path_metadata_refresh: Option<Instant>,
+
+    /// When tabs are renamed after the focus moved, if the name depends on it
+    next_focus_rename: Option<Instant>,
+
+    /// When the timeouts that have been set but haven't fired yet are due
+    scheduled_timers: RefCell<Vec<Instant>>,
+
+    /// The name of the session, set by the `ModeUpdate` event
+    session_name: Option<String>,
+
+    /// The process id of the zellij server, which tells apart sessions resurrected under the
+    /// same name
+    zellij_pid: Option<u32>,
+
+    /// Panes whose persisted state was restored but that no `PaneUpdate` has reported yet
+    restored_pane_ids: BTreeSet<u32>,
+
+    /// The names of the tabs whose lock or template was restored, by tab id, until a
+    /// `TabUpdate` confirms the tabs still have them
+    restored_tab_names: BTreeMap<usize, String>,
+
+    /// Whether the host directory the state is kept in is mounted at `STATE_DIR_MOUNT`
+    persistence_dir_mounted: bool,
+
+    /// Whether the persisted state of the session has been restored. Nothing is written before,
+    /// so the state of the previous plugin instance isn't overwritten.
+    persisted_state_restored: bool,
+
+    /// The environment variables zellij runs with that paths are shortened and the state dir is
+    /// found with, like `HOME`, detected once the permissions are granted
+    environment: BTreeMap<String, String>,
+
+    /// The state last written to the state dir, to skip writing it unchanged
+    last_persisted: RefCell<Option<String>>,
 }
 
-fn parse_pane_status(value: &str) -> Option<PaneStatus> {
-    match value {
-        "none" => Some(PaneStatus::None),
-        "waiting" => Some(PaneStatus::Waiting),
-        _ => None,
-    }
+/// How long to wait for the result of a command before running it again
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a command is run for the same path without getting a result
+const MAX_COMMAND_ATTEMPTS: u32 = 3;
+
+struct PendingCommand {
+    started_at: Instant,
+    attempts: u32,
 }
 
+/// How early a timer may fire and still count as due
+const TIMER_TOLERANCE: Duration = Duration::from_millis(50);
+
+register_plugin!(State);
+
 impl ZellijPlugin for State {
     fn load(&mut self, configuration: BTreeMap<String, String>) {
         self.userspace_configuration = configuration;
+        self.zellij_pid = Some(get_plugin_ids().zellij_pid);
+        self.tab_name_template = self.parse_tab_name_template();
+        self.statuses = StatusRegistry::from_configuration(&self.userspace_configuration);
+        self.path_rules = PathRules::from_configuration(&self.userspace_configuration);
+        self.path_prefixes = PathPrefixes::from_configuration(&self.userspace_configuration);
+        self.project_detectors =
+            ProjectDetectors::from_configuration(&self.userspace_configuration);
+        self.path_metadata
+            .set_capacity(self.path_metadata_cache_size());
         request_permission(&[
             PermissionType::ReadApplicationState,
             PermissionType::ChangeApplicationState,
             PermissionType::RunCommands,
+            PermissionType::FullHdAccess,
         ]);
         subscribe(&[
             EventType::TabUpdate,
@@ -151,7 +778,12 @@ impl ZellijPlugin for State {
             EventType::PermissionRequestResult,
             EventType::RunCommandResult,
             EventType::CwdChanged,
+            EventType::Timer,
+            EventType::ModeUpdate,
+            EventType::HostFolderChanged,
+            EventType::FailedToChangeHostFolder,
         ]);
+        self.schedule_path_metadata_refresh(Instant::now());
     }
 
     fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
@@ -166,34 +798,18 @@ impl ZellijPlugin for State {
             return false;
         };
 
-        let parts: Vec<&str> = payload.split(' ').collect();
-
-        if payload.starts_with("status ") {
-            if parts.len() != 3 {
-                eprintln!(
-                    "Expected exactly 3 parts for status update, got {}",
-                    parts.len()
-                );
-                return false;
-            }
-
-            let Ok(reported_pane_id) = rem_first_and_last(parts[1]).parse::<u32>() else {
-                eprintln!("Failed to parse pane id: {}", parts[1]);
-                return false;
-            };
-
-            let Some(pane_status) = parse_pane_status(rem_first_and_last(parts[2])) else {
-                let value = rem_first_and_last(parts[2]);
-                eprintln!("Unknown pane status: {value}");
-                return false;
-            };
+        let (format, command) = parse_pipe_command(&payload);
+        let result = command.and_then(|command| self.handle_pipe_command(command));
+        self.persist_state();
 
-            let pane_id = self.resolve_pipe_pane_id(reported_pane_id);
-
-            self.pane_statuses.insert(pane_id, pane_status);
-            self.organize();
+        if let Err(err) = &result {
+            eprintln!("Failed to handle pipe message {payload:?}: {err}");
+        }
 
-            return false;
+        if let (Some(reply), PipeSource::Cli(pipe_id)) =
+            (format.reply(&result), &pipe_message.source)
+        {
+            cli_pipe_output(pipe_id, &reply);
         }
 
         false
@@ -203,9 +819,25 @@ impl ZellijPlugin for State {
         match event {
             Event::TabUpdate(tab_info) => {
                 self.tabs = tab_info;
+                self.confirm_restored_tabs();
+                if self.detect_manual_renames() {
+                    self.organize();
+                }
+                self.handle_focus_change(Instant::now());
             }
             Event::PaneUpdate(data) => {
+                let commands = self
+                    .templates_use(Placeholder::Command)
+                    .then(|| self.tab_commands());
+
                 self.panes = data;
+                self.forget_closed_restored_panes();
+                self.handle_focus_change(Instant::now());
+
+                // Pane titles change with the program running in the pane
+                if commands.is_some_and(|commands| commands != self.tab_commands()) {
+                    self.organize();
+                }
             }
             Event::PaneClosed(pane_id_enum) => {
                 self.handle_pane_closed(pane_id_enum);
@@ -214,11 +846,28 @@ impl ZellijPlugin for State {
                 let pane_id = match pane_id_enum {
                     PaneId::Terminal(id) | PaneId::Plugin(id) => id,
                 };
+                // Re-validate cached paths, e.g. after `git init` in a directory known to be outside git
+                if self.path_metadata.get(&cwd).is_some() {
+                    self.fetch_git_path_metadata(cwd.clone());
+                }
                 self.pane_working_dirs.insert(pane_id, cwd);
                 self.organize();
             }
+            Event::Timer(_) => {
+                self.handle_timer(Instant::now());
+            }
+            Event::ModeUpdate(mode_info) => {
+                self.handle_session_name(mode_info.session_name);
+            }
+            Event::HostFolderChanged(_) => {
+                self.handle_state_dir_mounted();
+            }
+            Event::FailedToChangeHostFolder(err) => {
+                eprintln!("Failed to mount the state dir: {err:?}");
+            }
             Event::PermissionRequestResult(status) => {
                 self.permissions = Some(status);
+                self.detect_environment();
             }
             Event::RunCommandResult(exit_code, stdout, stderr, context) => {
                 return self.handle_run_command_result(exit_code, stdout, stderr, &context);
@@ -226,6 +875,8 @@ impl ZellijPlugin for State {
             _ => (),
         }
 
+        self.persist_state();
+
         false
     }
 
@@ -233,6 +884,67 @@ impl ZellijPlugin for State {
 }
 
 impl State {
+    fn handle_pipe_command(&mut self, command: PipeCommand) -> Result<(), String> {
+        match command {
+            PipeCommand::SetStatus {
+                pane_id,
+                status,
+                ttl,
+                on_expire,
+            } => {
+                let Some(pane_status) = self.parse_pane_status(&status) else {
+                    return Err(format!("Unknown pane status: {status}"));
+                };
+
+                if let Some(on_expire) = &on_expire {
+                    if self.parse_pane_status(on_expire).is_none() {
+                        return Err(format!("Unknown pane status: {on_expire}"));
+                    }
+                }
+
+                let now = Instant::now();
+                let pane_status = PaneStatus {
+                    expires_at: ttl.map(|ttl| now + ttl),
+                    on_expire,
+                    ..PaneStatus::new(&pane_status.name, now)
+                };
+
+                let pane_id = self.resolve_pipe_pane_id(pane_id);
+
+                if pane_status.name == status::NONE {
+                    self.pane_statuses.remove(&pane_id);
+                } else {
+                    self.pane_statuses.insert(pane_id, pane_status);
+                }
+
+                self.apply_focus_transition(now);
+                self.schedule_next_timer(now);
+            }
+            PipeCommand::Refresh => {
+                self.pending_commands.borrow_mut().clear();
+                for path in self.path_metadata.paths() {
+                    self.fetch_git_path_metadata(path);
+                }
+            }
+            PipeCommand::Lock { tab_id } => {
+                let tab_id = self.resolve_pipe_tab_id(tab_id)?;
+                if let Some(label) = self.tab_label(tab_id) {
+                    self.locked_tabs.entry(tab_id).or_insert(label);
+                }
+            }
+            PipeCommand::Unlock { tab_id } => {
+                let tab_id = self.resolve_pipe_tab_id(tab_id)?;
+                self.locked_tabs.remove(&tab_id);
+                self.tab_templates.remove(&tab_id);
+                self.applied_tab_names.borrow_mut().remove(&tab_id);
+            }
+        }
+
+        self.organize();
+
+        Ok(())
+    }
+
     fn handle_pane_closed(&mut self, pane_id_enum: PaneId) {
         let pane_id = match pane_id_enum {
             PaneId::Terminal(pane_id) | PaneId::Plugin(pane_id) => pane_id,
@@ -253,255 +965,1420 @@ impl State {
 
         self.pane_working_dirs.remove(&pane_id);
         self.pane_statuses.remove(&pane_id);
+        self.pane_focused_at.remove(&pane_id);
+        self.named_focus_at.remove(&pane_id);
         self.organize();
     }
 
-    fn handle_run_command_result(
-        &mut self,
-        exit_code: Option<i32>,
-        stdout: Vec<u8>,
-        stderr: Vec<u8>,
-        context: &BTreeMap<String, String>,
-    ) -> bool {
-        if context.get("plugin") != Some(&String::from("tabula")) {
-            return false;
+    /// The tab a lock command refers to: the given one if it exists, or the active one.
+    fn resolve_pipe_tab_id(&self, tab_id: Option<usize>) -> Result<usize, String> {
+        match tab_id {
+            Some(tab_id) if self.tabs.iter().any(|tab| tab.tab_id == tab_id) => Ok(tab_id),
+            Some(tab_id) => Err(format!("Unknown tab id: {tab_id}")),
+            None => self
+                .tabs
+                .iter()
+                .find(|tab| tab.active)
+                .map(|tab| tab.tab_id)
+                .ok_or_else(|| String::from("No active tab")),
         }
+    }
 
-        let Some((path, metadata)) =
-            Self::parse_git_path_metadata_output(exit_code, stdout, stderr, context)
-        else {
-            return false;
-        };
+    /// Locks the tabs whose name differs from the one tabula gave them, if the policy says so,
+    /// and forgets about closed tabs. A new name that is a template, e.g. `api: {auto}`, is used
+    /// to name the tab from then on instead. Returns whether a tab got a template.
+    fn detect_manual_renames(&mut self) -> bool {
+        let policy = self.manual_rename_policy();
+        let tab_ids: BTreeSet<usize> = self.tabs.iter().map(|tab| tab.tab_id).collect();
+        let mut applied_tab_names = self.applied_tab_names.borrow_mut();
+
+        applied_tab_names.retain(|tab_id, _| tab_ids.contains(tab_id));
+        self.locked_tabs
+            .retain(|tab_id, _| tab_ids.contains(tab_id));
+        self.tab_templates
+            .retain(|tab_id, _| tab_ids.contains(tab_id));
+
+        let mut found_template = false;
+
+        for tab in &self.tabs {
+            let renamed = match applied_tab_names.get_mut(&tab.tab_id) {
+                Some(applied) if tab.name == applied.name => {
+                    applied.previous_names.clear();
+                    continue;
+                }
+                Some(applied) if applied.previous_names.contains(&tab.name) => continue,
+                Some(_) => true,
+                // A locked tab tabula hasn't renamed since has the name it was locked with
+                None => self.locked_tabs.get(&tab.tab_id).is_some_and(|label| {
+                    *label != tab.name && self.locked_tab_name(tab, label) != tab.name
+                }),
+            };
 
-        self.path_metadata.insert(path, metadata);
-        self.organize();
+            if renamed {
+                applied_tab_names.remove(&tab.tab_id);
+            }
 
-        false
+            if let Some(template) = parse_tab_template(&tab.name) {
+                self.locked_tabs.remove(&tab.tab_id);
+                self.tab_templates.insert(tab.tab_id, template);
+                found_template = true;
+                continue;
+            }
+
+            if renamed {
+                self.tab_templates.remove(&tab.tab_id);
+
+                let locked = self.locked_tabs.contains_key(&tab.tab_id);
+                if locked || policy == ManualRenamePolicy::Lock {
+                    eprintln!(
+                        "Tab {} was renamed to {:?}, locking it",
+                        tab.tab_id, tab.name
+                    );
+                    let label = self.tab_label(tab.tab_id).unwrap_or_default();
+                    self.locked_tabs.insert(tab.tab_id, label);
+                }
+            }
+        }
+
+        found_template
     }
 
-    fn parse_git_path_metadata_output(
-        exit_code: Option<i32>,
-        stdout: Vec<u8>,
-        stderr: Vec<u8>,
-        context: &BTreeMap<String, String>,
-    ) -> Option<(PathBuf, PathMetadata)> {
-        let Some(fn_name) = context.get("fn") else {
-            eprintln!("Expected fn in context, got none");
-            return None;
+    /// Restores the state persisted for the session once its name is known, and moves it along
+    /// when the session is renamed.
+    fn handle_session_name(&mut self, session_name: Option<String>) {
+        let Some(session_name) = session_name else {
+            return;
         };
 
-        if exit_code != Some(0) {
-            eprintln!(
-                "Failed to run {}: exit_code: {:?}, stdout: {:?}, stderr: {:?}",
-                fn_name,
-                exit_code,
-                String::from_utf8(stdout),
-                String::from_utf8(stderr)
-            );
-
-            return None;
+        if self.session_name.as_ref() == Some(&session_name) {
+            return;
         }
 
-        let Ok(stdout) = String::from_utf8(stdout) else {
-            eprintln!("Failed to parse stdout for {fn_name}");
-            return None;
-        };
+        let previous = self.session_name.replace(session_name);
 
-        if fn_name != "get_git_path_metadata" {
-            eprintln!("Unexpected fn: {fn_name}");
-            return None;
+        if let (Some(previous), Some(zellij_pid), true) =
+            (previous, self.zellij_pid, self.persisted_state_restored)
+        {
+            // The session was renamed, the state is written under the new name
+            persistence::remove(Path::new(STATE_DIR_MOUNT), &previous, zellij_pid);
+            self.last_persisted.replace(None);
         }
 
-        let Some(path) = context.get("path") else {
-            eprintln!("Expected path in context, got none");
-            return None;
+        self.restore_persisted_state();
+    }
+
+    /// Creates the state dir on the host, to mount it once it exists.
+    fn create_state_dir(&self) {
+        let Some(state_dir) = self.host_state_dir() else {
+            return;
         };
 
-        let mut stdout_lines = stdout.trim().lines();
+        self.run_path_command(
+            "create_state_dir",
+            &["mkdir", "-p", &state_dir.to_string_lossy()],
+            PathBuf::from("/"),
+        );
+    }
 
-        let Some(git_worktree_root) = stdout_lines.next().map(PathBuf::from) else {
-            eprintln!("Expected git worktree root for {fn_name}");
-            return None;
-        };
+    fn host_state_dir(&self) -> Option<PathBuf> {
+        persistence::state_dir(
+            self.environment.get("XDG_STATE_HOME").map(String::as_str),
+            self.home_dir(),
+        )
+    }
+
+    /// Deletes the state of sessions whose zellij server is gone and restores the state of this
+    /// one, once the state dir is mounted.
+    fn handle_state_dir_mounted(&mut self) {
+        self.persistence_dir_mounted = true;
+
+        if let Some(zellij_pid) = self.zellij_pid {
+            let pids: Vec<String> =
+                persistence::other_servers(Path::new(STATE_DIR_MOUNT), zellij_pid)
+                    .iter()
+                    .map(u32::to_string)
+                    .collect();
+
+            if !pids.is_empty() {
+                let mut command = vec!["sh", "-c", DEAD_PROCESSES_SCRIPT, "sh"];
+                command.extend(pids.iter().map(String::as_str));
+                self.run_path_command("find_dead_servers", &command, PathBuf::from("/"));
+            }
+        }
+
+        self.restore_persisted_state();
+    }
+
+    /// Restores the state persisted for the session once its name is known and the state dir
+    /// is mounted.
+    fn restore_persisted_state(&mut self) {
+        if self.persisted_state_restored || !self.persistence_dir_mounted {
+            return;
+        }
+
+        let (Some(session_name), Some(zellij_pid)) = (&self.session_name, self.zellij_pid) else {
+            return;
+        };
+
+        let persisted = persistence::load(Path::new(STATE_DIR_MOUNT), session_name, zellij_pid);
+        self.persisted_state_restored = true;
+
+        if let Some(persisted) = persisted {
+            self.restore_state(persisted, Instant::now(), SystemTime::now());
+            self.organize();
+        }
+    }
+
+    /// Adds the persisted statuses and working dirs of panes that haven't been reported since
+    /// the plugin was loaded. Those of panes that are gone are dropped once the panes are known.
+    fn restore_state(&mut self, persisted: PersistedState, now: Instant, system_now: SystemTime) {
+        for (pane_id, pane_status) in persisted.pane_statuses {
+            if self.parse_pane_status(&pane_status.name).is_none() {
+                continue;
+            }
+
+            if let Entry::Vacant(entry) = self.pane_statuses.entry(pane_id) {
+                entry.insert(pane_status.restore(now, system_now));
+                self.restored_pane_ids.insert(pane_id);
+            }
+        }
+
+        for (pane_id, working_dir) in persisted.pane_working_dirs {
+            if let Entry::Vacant(entry) = self.pane_working_dirs.entry(pane_id) {
+                entry.insert(working_dir);
+                self.restored_pane_ids.insert(pane_id);
+            }
+        }
+
+        self.forget_closed_restored_panes();
+
+        for (tab_id, lock) in persisted.locked_tabs {
+            if let Entry::Vacant(entry) = self.locked_tabs.entry(tab_id) {
+                entry.insert(lock.label);
+                self.restored_tab_names.insert(tab_id, lock.name);
+            }
+        }
+
+        for (tab_id, persisted_template) in persisted.tab_templates {
+            let Some(template) = parse_tab_template(&persisted_template.template) else {
+                continue;
+            };
+
+            if let Entry::Vacant(entry) = self.tab_templates.entry(tab_id) {
+                entry.insert(template);
+                self.restored_tab_names
+                    .entry(tab_id)
+                    .or_insert(persisted_template.name);
+            }
+        }
+
+        self.confirm_restored_tabs();
+        self.schedule_next_timer(now);
+    }
+
+    /// Drops the restored state of panes the first `PaneUpdate` since the restore doesn't list,
+    /// e.g. because they were closed while the plugin wasn't running.
+    fn forget_closed_restored_panes(&mut self) {
+        if self.restored_pane_ids.is_empty() || self.panes.panes.is_empty() {
+            return;
+        }
+
+        let pane_ids: BTreeSet<u32> = self
+            .panes
+            .panes
+            .values()
+            .flatten()
+            .filter(|pane| !pane.is_plugin)
+            .map(|pane| pane.id)
+            .collect();
+
+        for pane_id in std::mem::take(&mut self.restored_pane_ids) {
+            if !pane_ids.contains(&pane_id) {
+                self.pane_statuses.remove(&pane_id);
+                self.pane_working_dirs.remove(&pane_id);
+            }
+        }
+    }
+
+    /// Keeps the restored locks and templates of the tabs the first `TabUpdate` since the
+    /// restore lists with the name they had when they were persisted. A lock is dropped as well
+    /// if that's the name tabula would give the tab anyway.
+    fn confirm_restored_tabs(&mut self) {
+        if self.restored_tab_names.is_empty() || self.tabs.is_empty() {
+            return;
+        }
+
+        for (tab_id, name) in std::mem::take(&mut self.restored_tab_names) {
+            let Some(tab) = self
+                .tabs
+                .iter()
+                .find(|tab| tab.tab_id == tab_id && tab.name == name)
+            else {
+                self.locked_tabs.remove(&tab_id);
+                self.tab_templates.remove(&tab_id);
+                continue;
+            };
+
+            if self.rendered_tab_name(tab).as_ref() == Some(&tab.name) {
+                self.locked_tabs.remove(&tab_id);
+            }
+        }
+    }
+
+    /// The current name of a tab, or the one it had when its state was persisted if the tabs
+    /// aren't known yet.
+    fn known_tab_name(&self, tab_id: usize) -> Option<&String> {
+        self.tabs
+            .iter()
+            .find(|tab| tab.tab_id == tab_id)
+            .map(|tab| &tab.name)
+            .or_else(|| self.restored_tab_names.get(&tab_id))
+    }
+
+    /// Writes the pane statuses and working dirs to the plugin's data directory if they changed.
+    fn persist_state(&self) {
+        if !self.persisted_state_restored {
+            return;
+        }
+
+        let (Some(session_name), Some(zellij_pid)) = (&self.session_name, self.zellij_pid) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let system_now = SystemTime::now();
+        let state = PersistedState {
+            pane_statuses: self
+                .pane_statuses
+                .iter()
+                .map(|(pane_id, pane_status)| {
+                    (
+                        *pane_id,
+                        PersistedPaneStatus::capture(pane_status, now, system_now),
+                    )
+                })
+                .collect(),
+            pane_working_dirs: self.pane_working_dirs.clone(),
+            locked_tabs: self
+                .locked_tabs
+                .iter()
+                .filter_map(|(tab_id, label)| {
+                    let lock = PersistedLock {
+                        label: label.clone(),
+                        name: self.known_tab_name(*tab_id)?.clone(),
+                    };
+                    Some((*tab_id, lock))
+                })
+                .collect(),
+            tab_templates: self
+                .tab_templates
+                .iter()
+                .filter_map(|(tab_id, template)| {
+                    let persisted_template = PersistedTabTemplate {
+                        template: template.source().to_string(),
+                        name: self.known_tab_name(*tab_id)?.clone(),
+                    };
+                    Some((*tab_id, persisted_template))
+                })
+                .collect(),
+        };
+
+        let mut last_persisted = self.last_persisted.borrow_mut();
+        if let Some(contents) = persistence::save(
+            Path::new(STATE_DIR_MOUNT),
+            session_name,
+            zellij_pid,
+            &state,
+            last_persisted.as_deref(),
+        ) {
+            *last_persisted = Some(contents);
+        }
+    }
+
+    /// Tracks which pane is focused and applies the `on_focus` transition of its status.
+    fn handle_focus_change(&mut self, now: Instant) {
+        let focus_moved = self.track_focused_panes(now) && self.names_follow_focus();
+        let debounce = self.focus_rename_debounce();
+
+        if focus_moved && !debounce.is_zero() {
+            // Renaming is delayed until the focus has settled
+            self.next_focus_rename = Some(now + debounce);
+        }
+
+        if self.next_focus_rename.is_none() {
+            self.named_focus_at.clone_from(&self.pane_focused_at);
+
+            if focus_moved {
+                self.organize();
+            }
+        }
+
+        let focused_pane_id = self.focused_pane_id();
+
+        if self.focused_pane.map(|(pane_id, _)| pane_id) != focused_pane_id {
+            self.focused_pane = focused_pane_id.map(|pane_id| (pane_id, now));
+        }
+
+        if self.apply_focus_transition(now) {
+            self.organize();
+        }
+
+        self.schedule_next_timer(now);
+    }
+
+    /// Whether tab names depend on which pane of the tab is focused.
+    fn names_follow_focus(&self) -> bool {
+        self.multi_pane_strategy().follows_focus() || self.templates_use(Placeholder::Command)
+    }
+
+    /// The focused terminal pane of the active tab.
+    fn focused_pane_id(&self) -> Option<u32> {
+        let tab = self.tabs.iter().find(|tab| tab.active)?;
+        self.focused_pane_in_tab(tab).map(|pane| pane.id)
+    }
+
+    /// The focused terminal pane of a tab, which is the one that gets focused when switching to
+    /// the tab.
+    fn focused_pane_in_tab(&self, tab: &TabInfo) -> Option<&PaneInfo> {
+        self.panes.panes.get(&tab.position)?.iter().find(|pane| {
+            pane.is_focused
+                && !pane.is_plugin
+                && !pane.is_suppressed
+                && pane.is_floating == tab.are_floating_panes_visible
+        })
+    }
+
+    /// Orders panes from the least to the most recently focused, with the focused pane of a tab
+    /// last if no focus changes have been seen yet. While a focus rename is debounced, panes are
+    /// ordered as they were before the focus moved.
+    fn focus_recency(&self, pane: &PaneInfo) -> (Option<Instant>, bool) {
+        (self.named_focus_at.get(&pane.id).copied(), pane.is_focused)
+    }
+
+    /// Remembers when the focused pane of each tab got focused. Returns whether the focus moved
+    /// away from another pane in any tab, which isn't the case for the first focused pane.
+    fn track_focused_panes(&mut self, now: Instant) -> bool {
+        let mut newly_focused = Vec::new();
+        let mut focus_moved = false;
+
+        for tab in &self.tabs {
+            let Some(focused) = self.focused_pane_in_tab(tab) else {
+                continue;
+            };
+
+            let focused_at = self.pane_focused_at.get(&focused.id);
+            let other_focused_since = self.panes.panes[&tab.position].iter().any(|pane| {
+                pane.id != focused.id
+                    && self
+                        .pane_focused_at
+                        .get(&pane.id)
+                        .is_some_and(|other_focused_at| Some(other_focused_at) >= focused_at)
+            });
+
+            if focused_at.is_none() || other_focused_since {
+                newly_focused.push(focused.id);
+            }
+            focus_moved |= other_focused_since;
+        }
+
+        for pane_id in newly_focused {
+            self.pane_focused_at.insert(pane_id, now);
+        }
+
+        focus_moved
+    }
+
+    /// When the status of the focused pane switches to its `on_focus` status.
+    fn next_focus_transition(&self) -> Option<Instant> {
+        let (pane_id, focused_since) = self.focused_pane?;
+        let pane_status = self.pane_statuses.get(&pane_id)?;
+
+        if pane_status.set_on_focus {
+            return None;
+        }
+
+        let definition = self.statuses.get(&pane_status.name)?;
+        definition.on_focus.as_ref()?;
+
+        Some(focused_since.max(pane_status.set_at) + definition.focus_delay)
+    }
+
+    /// Switches the status of the focused pane to its `on_focus` status if it is due. Returns
+    /// whether the status changed.
+    fn apply_focus_transition(&mut self, now: Instant) -> bool {
+        let Some(due) = self.next_focus_transition() else {
+            return false;
+        };
+
+        if due > now + TIMER_TOLERANCE {
+            return false;
+        }
+
+        let Some((pane_id, _)) = self.focused_pane else {
+            return false;
+        };
+
+        let on_focus = self
+            .pane_statuses
+            .get(&pane_id)
+            .and_then(|pane_status| self.statuses.get(&pane_status.name))
+            .and_then(|definition| definition.on_focus.clone())
+            .unwrap_or_else(|| status::NONE.to_string());
+
+        if on_focus == status::NONE {
+            self.pane_statuses.remove(&pane_id);
+        } else if self.statuses.get(&on_focus).is_some() {
+            self.pane_statuses.insert(
+                pane_id,
+                PaneStatus {
+                    set_on_focus: true,
+                    ..PaneStatus::new(&on_focus, now)
+                },
+            );
+        } else {
+            eprintln!("Unknown on_focus status: {on_focus}, clearing the status instead");
+            self.pane_statuses.remove(&pane_id);
+        }
+
+        true
+    }
+
+    fn handle_timer(&mut self, now: Instant) {
+        self.scheduled_timers
+            .borrow_mut()
+            .retain(|due| *due > now + TIMER_TOLERANCE);
+
+        if self
+            .next_path_metadata_refresh
+            .is_some_and(|due| due <= now + TIMER_TOLERANCE)
+        {
+            self.refresh_path_metadata(now);
+            self.schedule_path_metadata_refresh(now);
+        }
+
+        let focus_settled = self
+            .next_focus_rename
+            .is_some_and(|due| due <= now + TIMER_TOLERANCE);
+        if focus_settled {
+            self.next_focus_rename = None;
+            self.named_focus_at.clone_from(&self.pane_focused_at);
+        }
+
+        let expired = self.expire_pane_statuses(now);
+        let transitioned = self.apply_focus_transition(now);
+
+        if focus_settled || expired || transitioned {
+            self.organize();
+        }
+
+        self.schedule_next_timer(now);
+    }
+
+    /// Clears expired pane statuses or switches them to their `on_expire` status. Returns whether
+    /// any status changed.
+    fn expire_pane_statuses(&mut self, now: Instant) -> bool {
+        let expired: Vec<(u32, Option<String>)> = self
+            .pane_statuses
+            .iter()
+            .filter(|(_, pane_status)| {
+                pane_status
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= now + TIMER_TOLERANCE)
+            })
+            .map(|(pane_id, pane_status)| (*pane_id, pane_status.on_expire.clone()))
+            .collect();
+
+        for (pane_id, on_expire) in &expired {
+            match on_expire {
+                Some(on_expire) if on_expire != status::NONE => {
+                    self.pane_statuses
+                        .insert(*pane_id, PaneStatus::new(on_expire, now));
+                }
+                _ => {
+                    self.pane_statuses.remove(pane_id);
+                }
+            }
+        }
+
+        !expired.is_empty()
+    }
+
+    /// When the next pane status expires.
+    fn next_status_expiry(&self) -> Option<Instant> {
+        self.pane_statuses
+            .values()
+            .filter_map(|pane_status| pane_status.expires_at)
+            .min()
+    }
+
+    /// Sets a timeout for the next due timer, unless one that fires before it is already set.
+    ///
+    /// Zellij's timers can't be told apart or cancelled, so every timer checks everything that
+    /// is due and then schedules the next one.
+    fn schedule_next_timer(&self, now: Instant) {
+        let Some(due) = [
+            self.next_path_metadata_refresh,
+            self.next_focus_rename,
+            self.next_focus_transition(),
+            self.next_status_expiry(),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied() else {
+            return;
+        };
+
+        let mut scheduled_timers = self.scheduled_timers.borrow_mut();
+
+        if scheduled_timers.iter().any(|scheduled| *scheduled <= due) {
+            return;
+        }
+
+        scheduled_timers.push(due);
+        set_timeout(due.saturating_duration_since(now).as_secs_f64());
+    }
+
+    fn handle_run_command_result(
+        &mut self,
+        exit_code: Option<i32>,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+        context: &BTreeMap<String, String>,
+    ) -> bool {
+        if context.get("plugin") != Some(&String::from("tabula")) {
+            return false;
+        }
+
+        let Some(fn_name) = context.get("fn") else {
+            eprintln!("Expected fn in context, got none");
+            return false;
+        };
+
+        let Some(path) = context.get("path").map(PathBuf::from) else {
+            eprintln!("Expected path in context, got none");
+            return false;
+        };
+
+        self.pending_commands
+            .borrow_mut()
+            .remove(&(fn_name.clone(), path.clone()));
+
+        let Some(stdout) = Self::parse_command_stdout(fn_name, exit_code, stdout, stderr) else {
+            self.handle_command_failure(fn_name, path);
+            return false;
+        };
+
+        match fn_name.as_str() {
+            "get_git_path_metadata" => {
+                let Some(mut metadata) = Self::parse_git_path_metadata_output(&stdout) else {
+                    return false;
+                };
+
+                // Keep showing the previous HEAD and status until they have been refreshed too
+                if let Some(PathKind::Git(previous)) = self.path_metadata.remove(&path) {
+                    metadata.head = previous.head;
+                    metadata.status = previous.status;
+                    metadata.package = previous.package;
+                }
+
+                let root = metadata.git_worktree_root.clone();
+                self.path_metadata
+                    .insert(path.clone(), PathKind::Git(metadata));
+                self.get_git_head_state(path.clone());
+                if self.templates_use(Placeholder::GitStatus) {
+                    self.get_git_status(path.clone());
+                }
+                self.get_package(path, &root);
+            }
+            "get_git_head_state" => {
+                let Some(PathKind::Git(metadata)) = self.path_metadata.get_mut(&path) else {
+                    return false;
+                };
+
+                metadata.head = parse_git_head_state_output(&stdout);
+            }
+            "get_git_status" => {
+                let Some(PathKind::Git(metadata)) = self.path_metadata.get_mut(&path) else {
+                    return false;
+                };
+
+                metadata.status = Some(parse_git_status_output(&stdout));
+            }
+            "detect_project" => {
+                let Some(mut metadata) = parse_project_root_output(&path, &stdout) else {
+                    return false;
+                };
+
+                if let Some(PathKind::Project(previous)) = self.path_metadata.remove(&path) {
+                    metadata.package = previous.package;
+                }
+
+                let root = metadata.git_worktree_root.clone();
+                self.path_metadata
+                    .insert(path.clone(), PathKind::Project(metadata));
+                self.get_package(path, &root);
+            }
+            "get_package" => {
+                let Some(PathKind::Git(metadata) | PathKind::Project(metadata)) =
+                    self.path_metadata.get_mut(&path)
+                else {
+                    return false;
+                };
+
+                metadata.package = parse_package_output(&stdout);
+            }
+            "get_environment" => {
+                self.environment = parse_environment_output(&self.environment_variables(), &stdout);
+                self.create_state_dir();
+            }
+            "create_state_dir" => {
+                if let Some(state_dir) = self.host_state_dir() {
+                    change_host_folder(state_dir);
+                }
+                return false;
+            }
+            "find_dead_servers" => {
+                let pids = stdout
+                    .lines()
+                    .filter_map(|line| line.trim().parse().ok())
+                    .collect();
+                persistence::remove_servers(Path::new(STATE_DIR_MOUNT), &pids);
+                return false;
+            }
+            _ => {
+                eprintln!("Unexpected fn: {fn_name}");
+                return false;
+            }
+        }
+
+        self.organize();
+
+        false
+    }
+
+    fn handle_command_failure(&mut self, fn_name: &str, path: PathBuf) {
+        match fn_name {
+            "get_git_path_metadata" => {
+                // Keep showing a project that is still being detected again
+                if let Some(PathKind::Project(_)) = self.path_metadata.get(&path) {
+                    self.detect_project(path);
+                    return;
+                }
+
+                let previous = self.path_metadata.remove(&path);
+                self.path_metadata.insert(path.clone(), PathKind::NotGit);
+                self.detect_project(path);
+
+                // The path is no longer inside a git worktree, e.g. because the worktree was deleted
+                if let Some(PathKind::Git(_)) = previous {
+                    self.organize();
+                }
+            }
+            "detect_project" => {
+                let previous = self.path_metadata.remove(&path);
+                self.path_metadata.insert(path, PathKind::NotGit);
+
+                if let Some(PathKind::Project(_)) = previous {
+                    self.organize();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn parse_command_stdout(
+        fn_name: &str,
+        exit_code: Option<i32>,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    ) -> Option<String> {
+        if exit_code != Some(0) {
+            eprintln!(
+                "Failed to run {}: exit_code: {:?}, stdout: {:?}, stderr: {:?}",
+                fn_name,
+                exit_code,
+                String::from_utf8(stdout),
+                String::from_utf8(stderr)
+            );
+
+            return None;
+        }
+
+        let Ok(stdout) = String::from_utf8(stdout) else {
+            eprintln!("Failed to parse stdout for {fn_name}");
+            return None;
+        };
+
+        Some(stdout)
+    }
+
+    fn parse_git_path_metadata_output(stdout: &str) -> Option<PathMetadata> {
+        let fn_name = "get_git_path_metadata";
+
+        let mut stdout_lines = stdout.trim().lines();
+
+        let Some(git_worktree_root) = stdout_lines.next().map(PathBuf::from) else {
+            eprintln!("Expected git worktree root for {fn_name}");
+            return None;
+        };
 
         let Some(git_common_dir) = stdout_lines.next().map(PathBuf::from) else {
             eprintln!("Expected git common dir for {fn_name}");
             return None;
         };
 
-        let fallback_repo_name = git_worktree_root
-            .file_name()
-            .and_then(|repo_name| repo_name.to_str())
-            .map(str::to_owned);
+        // Only printed inside a submodule
+        let superproject_root = stdout_lines.next().map(PathBuf::from);
+
+        let fallback_repo_name = git_worktree_root
+            .file_name()
+            .and_then(|repo_name| repo_name.to_str())
+            .map(str::to_owned);
+
+        // The git dir of a submodule is inside the `.git/modules` dir of the superproject
+        let repo_name = if superproject_root.is_some() {
+            fallback_repo_name
+        } else {
+            git_common_dir
+                .parent()
+                .and_then(|repo_dir| repo_dir.file_name())
+                .and_then(|repo_name| repo_name.to_str())
+                .map(str::to_owned)
+                .or(fallback_repo_name)
+        };
+
+        let Some(repo_name) = repo_name else {
+            eprintln!("Expected repo name for {fn_name}");
+            return None;
+        };
+
+        let Some(worktree_name) = git_worktree_root
+            .file_name()
+            .and_then(|worktree_name| worktree_name.to_str())
+            .map(str::to_owned)
+        else {
+            eprintln!("Expected worktree name for {fn_name}");
+            return None;
+        };
+
+        Some(PathMetadata {
+            git_worktree_root,
+            repo_name,
+            worktree_name,
+            head: GitHead::default(),
+            status: None,
+            package: None,
+            superproject_root,
+        })
+    }
+
+    fn resolve_pipe_pane_id(&self, reported_pane_id: u32) -> u32 {
+        let terminal_panes: Vec<&PaneInfo> = self
+            .panes
+            .panes
+            .values()
+            .flat_map(|pane_list| pane_list.iter())
+            .filter(|pane| !pane.is_plugin && !pane.is_suppressed)
+            .collect();
+
+        if terminal_panes
+            .iter()
+            .any(|pane| pane.id == reported_pane_id)
+        {
+            return reported_pane_id;
+        }
+
+        let focused_panes: Vec<&PaneInfo> = terminal_panes
+            .into_iter()
+            .filter(|pane| pane.is_focused)
+            .collect();
+
+        if focused_panes.len() == 1 {
+            let focused_pane_id = focused_panes[0].id;
+            eprintln!(
+                "Falling back from reported pane id {reported_pane_id} to focused pane id \
+                 {focused_pane_id}"
+            );
+            return focused_pane_id;
+        }
+
+        reported_pane_id
+    }
+
+    fn get_git_path_metadata(&self, path: PathBuf) -> Option<PathMetadata> {
+        match self.path_metadata.get(&path) {
+            Some(PathKind::Git(metadata) | PathKind::Project(metadata)) => Some(metadata.clone()),
+            Some(PathKind::NotGit) => None,
+            None => {
+                self.fetch_git_path_metadata(path);
+
+                None
+            }
+        }
+    }
+
+    /// Looks up the workspace member `path` is in, below the project `root`, if the templates
+    /// show it.
+    fn get_package(&self, path: PathBuf, root: &Path) {
+        if !self.templates_use(Placeholder::Package)
+            && !self.templates_use(Placeholder::PackagePath)
+        {
+            return;
+        }
+
+        let root = root.to_string_lossy();
+        self.run_path_command(
+            "get_package",
+            &["sh", "-c", PACKAGE_SCRIPT, "sh", &root],
+            path,
+        );
+    }
+
+    /// Looks for a project other than a git worktree containing `path`.
+    fn detect_project(&self, path: PathBuf) {
+        if self.project_detectors.is_empty() {
+            return;
+        }
+
+        let arguments = self.project_detectors.arguments();
+        let mut command = vec!["sh", "-c", PROJECT_ROOT_SCRIPT, "sh"];
+        command.extend(arguments.iter().map(String::as_str));
+
+        self.run_path_command("detect_project", &command, path);
+    }
+
+    /// Looks up the home directory and the environment variables used in `path_prefixes`.
+    fn detect_environment(&self) {
+        let mut command = vec!["sh", "-c", ENVIRONMENT_SCRIPT, "sh"];
+        command.extend(self.environment_variables());
+
+        self.run_path_command("get_environment", &command, PathBuf::from("/"));
+    }
+
+    fn environment_variables(&self) -> Vec<&str> {
+        let mut variables = vec!["HOME", "XDG_STATE_HOME"];
+        variables.extend(self.path_prefixes.variables());
+        variables.sort_unstable();
+        variables.dedup();
+        variables
+    }
+
+    fn fetch_git_path_metadata(&self, path: PathBuf) {
+        self.run_path_command(
+            "get_git_path_metadata",
+            &[
+                "git",
+                "rev-parse",
+                "--path-format=absolute",
+                "--show-toplevel",
+                "--git-common-dir",
+                "--show-superproject-working-tree",
+            ],
+            path,
+        );
+    }
+
+    /// Re-fetches cached metadata older than `path_metadata_ttl` (or `non_git_path_ttl` for
+    /// paths outside of git) that is still in use and drops the rest.
+    fn refresh_path_metadata(&mut self, now: Instant) {
+        let git_ttl = self.path_metadata_ttl();
+        let non_git_ttl = self.non_git_path_ttl();
+
+        let (used, unused) = self.path_metadata.expired(now, |kind| match kind {
+            PathKind::Git(_) | PathKind::Project(_) => git_ttl,
+            PathKind::NotGit => non_git_ttl,
+        });
+
+        for path in unused {
+            self.path_metadata.remove(&path);
+        }
+
+        for path in used {
+            self.fetch_git_path_metadata(path);
+        }
+    }
+
+    fn schedule_path_metadata_refresh(&mut self, now: Instant) {
+        let interval = self.path_metadata_refresh_interval();
+        self.next_path_metadata_refresh = (!interval.is_zero()).then(|| now + interval);
+        self.schedule_next_timer(now);
+    }
+
+    fn get_git_head_state(&self, path: PathBuf) {
+        self.run_path_command(
+            "get_git_head_state",
+            &["sh", "-c", GIT_HEAD_STATE_SCRIPT],
+            path,
+        );
+    }
+
+    fn get_git_status(&self, path: PathBuf) {
+        self.run_path_command(
+            "get_git_status",
+            &[
+                "git",
+                "status",
+                "--porcelain=v2",
+                "--branch",
+                "--show-stash",
+            ],
+            path,
+        );
+    }
+
+    /// Runs `command` in `path`, unless the same command is still running there.
+    ///
+    /// A command whose result hasn't arrived after `COMMAND_TIMEOUT` is retried, up to
+    /// `MAX_COMMAND_ATTEMPTS` times. After that it is only run again on an explicit refresh.
+    fn run_path_command(&self, fn_name: &str, command: &[&str], path: PathBuf) {
+        let Some(PermissionStatus::Granted) = self.permissions else {
+            return;
+        };
+
+        let now = Instant::now();
+        let key = (fn_name.to_string(), path.clone());
+        let mut pending_commands = self.pending_commands.borrow_mut();
+
+        let attempts = match pending_commands.get(&key) {
+            None => 1,
+            Some(pending) if pending.attempts >= MAX_COMMAND_ATTEMPTS => return,
+            Some(pending)
+                if now.saturating_duration_since(pending.started_at) < COMMAND_TIMEOUT =>
+            {
+                return;
+            }
+            Some(pending) => {
+                eprintln!(
+                    "Timed out waiting for {fn_name} in {}, retrying",
+                    path.display()
+                );
+                pending.attempts + 1
+            }
+        };
+
+        pending_commands.insert(
+            key,
+            PendingCommand {
+                started_at: now,
+                attempts,
+            },
+        );
+
+        let mut context = BTreeMap::new();
+        context.insert(String::from("plugin"), String::from("tabula"));
+        context.insert(String::from("fn"), String::from(fn_name));
+        context.insert(String::from("path"), String::from(path.to_string_lossy()));
+        run_command_with_env_variables_and_cwd(command, BTreeMap::new(), path, context);
+    }
+
+    /// The terminal panes of a tab that are shown.
+    fn tab_panes(&self, tab: &TabInfo) -> Vec<PaneInfo> {
+        self.panes
+            .panes
+            .get(&tab.position)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| !p.is_suppressed && !p.is_plugin)
+            .collect()
+    }
+
+    /// The `{command}` of each tab.
+    fn tab_commands(&self) -> Vec<Option<String>> {
+        self.tabs
+            .iter()
+            .map(|tab| self.tab_command(&self.tab_panes(tab)))
+            .collect()
+    }
+
+    /// The command running in the most recently focused of `panes`.
+    fn tab_command(&self, panes: &[PaneInfo]) -> Option<String> {
+        let ignored_commands = self.ignored_commands();
+        panes
+            .iter()
+            .max_by_key(|pane| self.focus_recency(pane))
+            .and_then(|pane| pane_command(pane, &ignored_commands))
+    }
+
+    fn organize(&self) {
+        for tab in &self.tabs {
+            let tab_name = if let Some(label) = self.locked_tabs.get(&tab.tab_id) {
+                self.locked_tab_name(tab, label)
+            } else if let Some(tab_name) = self.rendered_tab_name(tab) {
+                tab_name
+            } else {
+                // If there are no working dirs, skip this tab
+                continue;
+            };
+
+            if self.tabs[tab.position].name == tab_name {
+                continue;
+            }
+
+            let Some(rename_target) = u64::try_from(tab.tab_id).ok() else {
+                continue;
+            };
+
+            let mut applied_tab_names = self.applied_tab_names.borrow_mut();
+            let applied = applied_tab_names.entry(tab.tab_id).or_default();
+            let previous_name = std::mem::replace(&mut applied.name, tab_name.clone());
+            applied.previous_names.insert(previous_name);
+            applied.previous_names.insert(tab.name.clone());
+
+            rename_tab_with_id(rename_target, tab_name);
+        }
+    }
+
+    /// The name of a locked tab: the name the user gave it, prefixed with the status glyph if
+    /// `tab_name_template` shows it.
+    fn locked_tab_name(&self, tab: &TabInfo, label: &str) -> String {
+        format!("{}{label}", self.tab_status_glyph(tab))
+    }
+
+    /// The status glyph `tab_name_template` shows for a tab, or nothing.
+    fn tab_status_glyph(&self, tab: &TabInfo) -> &str {
+        if !self.tab_name_template.uses(Placeholder::Status) {
+            return "";
+        }
+
+        self.tab_status(&self.tab_panes(tab))
+            .map_or("", |status| status.glyph.as_str())
+    }
+
+    /// The name of a tab without the status glyph tabula shows in front of it, to lock it with.
+    fn tab_label(&self, tab_id: usize) -> Option<String> {
+        let tab = self.tabs.iter().find(|tab| tab.tab_id == tab_id)?;
+        let glyph = self.tab_status_glyph(tab);
+
+        Some(
+            tab.name
+                .strip_prefix(glyph)
+                .unwrap_or(&tab.name)
+                .to_string(),
+        )
+    }
+
+    /// The name tabula gives a tab, `None` if none of its panes has a known working dir.
+    fn rendered_tab_name(&self, tab: &TabInfo) -> Option<String> {
+        let panes = self.tab_panes(tab);
+        let context = self.tab_name_context(&panes)?;
+        Some(self.fitted_tab_name(tab.tab_id, context))
+    }
+
+    /// Whether `tab_name_template` or any template given as a tab name references `placeholder`.
+    fn templates_use(&self, placeholder: Placeholder) -> bool {
+        self.tab_name_template.uses(placeholder)
+            || self
+                .tab_templates
+                .values()
+                .any(|template| template.uses(placeholder))
+    }
+
+    /// Renders the name of a tab from the template it was given as its name, if any, or from
+    /// `tab_name_template`.
+    fn tab_name(&self, tab_id: usize, context: &TabNameContext) -> String {
+        let auto = self
+            .tab_name_template
+            .render(&|placeholder| context.value(placeholder));
+
+        match self.tab_templates.get(&tab_id) {
+            Some(template) => template.render(&|placeholder| match placeholder {
+                Placeholder::Auto => auto.clone(),
+                placeholder => context.value(placeholder),
+            }),
+            None => auto,
+        }
+    }
+
+    /// Renders the name of a tab, abbreviating its path until the name fits into
+    /// `max_tab_name_length` and truncating it if that's not enough.
+    fn fitted_tab_name(&self, tab_id: usize, mut context: TabNameContext) -> String {
+        let mut tab_name = self.tab_name(tab_id, &context);
 
-        let repo_name = git_common_dir
-            .parent()
-            .and_then(|repo_dir| repo_dir.file_name())
-            .and_then(|repo_name| repo_name.to_str())
-            .map(str::to_owned)
-            .or(fallback_repo_name);
+        let max_length = self.max_tab_name_length();
+        if max_length == 0 || abbreviation::width(&tab_name) <= max_length {
+            return tab_name;
+        }
 
-        let Some(repo_name) = repo_name else {
-            eprintln!("Expected repo name for {fn_name}");
-            return None;
-        };
+        let strategy = self.path_abbreviation();
 
-        let Some(worktree_name) = git_worktree_root
-            .file_name()
-            .and_then(|worktree_name| worktree_name.to_str())
-            .map(str::to_owned)
-        else {
-            eprintln!("Expected worktree name for {fn_name}");
-            return None;
-        };
+        for path in abbreviation::abbreviate_path(&context.path.display, strategy) {
+            context.path.display = path;
+            tab_name = self.tab_name(tab_id, &context);
 
-        Some((
-            PathBuf::from(path),
-            PathMetadata {
-                git_worktree_root,
-                repo_name,
-                worktree_name,
-            },
-        ))
+            if abbreviation::width(&tab_name) <= max_length {
+                return tab_name;
+            }
+        }
+
+        match strategy {
+            AbbreviationStrategy::Middle => abbreviation::truncate_middle(&tab_name, max_length),
+            _ => abbreviation::truncate_end(&tab_name, max_length),
+        }
     }
 
-    fn resolve_pipe_pane_id(&self, reported_pane_id: u32) -> u32 {
-        let terminal_panes: Vec<&PaneInfo> = self
-            .panes
-            .panes
-            .values()
-            .flat_map(|pane_list| pane_list.iter())
-            .filter(|pane| !pane.is_plugin && !pane.is_suppressed)
+    fn tab_name_context(&self, panes: &[PaneInfo]) -> Option<TabNameContext> {
+        let panes_with_dirs: Vec<(&PaneInfo, &PathBuf)> = panes
+            .iter()
+            .filter_map(|p| self.pane_working_dirs.get(&p.id).map(|dir| (p, dir)))
             .collect();
 
-        if terminal_panes
+        let (_, first_working_dir) = panes_with_dirs.first().copied()?;
+
+        let (dir, summary) = if panes_with_dirs.len() == 1 {
+            (first_working_dir.clone(), TabSummary::path(""))
+        } else if panes_with_dirs
             .iter()
-            .any(|pane| pane.id == reported_pane_id)
+            .all(|(_, dir)| *dir == first_working_dir)
         {
-            return reported_pane_id;
-        }
+            // If all working_dirs_in_tab are the same, use that as the tab name
+            (first_working_dir.clone(), TabSummary::path("/"))
+        } else {
+            self.summarize_working_dirs(&panes_with_dirs, panes.len())
+        };
 
-        let focused_panes: Vec<&PaneInfo> = terminal_panes
-            .into_iter()
-            .filter(|pane| pane.is_focused)
-            .collect();
+        let path = describe_path(self, &dir);
+
+        let command = self.tab_command(panes);
+
+        Some(TabNameContext {
+            status_glyph: self
+                .tab_status(panes)
+                .map(|status| status.glyph.clone())
+                .unwrap_or_default(),
+            path,
+            dir,
+            pane_count: panes.len(),
+            command,
+            summary,
+        })
+    }
 
-        if focused_panes.len() == 1 {
-            let focused_pane_id = focused_panes[0].id;
-            eprintln!(
-                "Falling back from reported pane id {reported_pane_id} to focused pane id \
-                 {focused_pane_id}"
-            );
-            return focused_pane_id;
+    /// The directory to name a tab after whose panes are in different directories, and how to
+    /// build its name, according to the `multi_pane_strategy`.
+    fn summarize_working_dirs(
+        &self,
+        panes_with_dirs: &[(&PaneInfo, &PathBuf)],
+        pane_count: usize,
+    ) -> (PathBuf, TabSummary) {
+        let working_dirs: Vec<&PathBuf> = panes_with_dirs.iter().map(|(_, dir)| *dir).collect();
+
+        let mut distinct_dirs: Vec<&PathBuf> = Vec::new();
+        for dir in &working_dirs {
+            if !distinct_dirs.contains(dir) {
+                distinct_dirs.push(dir);
+            }
         }
 
-        reported_pane_id
-    }
+        // Panes from the most to the least recently focused
+        let mut by_recency = panes_with_dirs.to_vec();
+        by_recency.sort_by_key(|(pane, _)| std::cmp::Reverse(self.focus_recency(pane)));
+        let last_focused_dir = by_recency[0].1;
+
+        match self.multi_pane_strategy() {
+            MultiPaneStrategy::CommonAncestor => (
+                common_ancestor(&working_dirs),
+                TabSummary::Path {
+                    path_suffix: "/*",
+                    annotation: format!(" ({pane_count} panes)"),
+                },
+            ),
+            MultiPaneStrategy::LastFocused => (last_focused_dir.clone(), TabSummary::path("")),
+            MultiPaneStrategy::Majority => {
+                let count = |dir: &PathBuf| working_dirs.iter().filter(|d| **d == dir).count();
+                let majority = distinct_dirs.iter().map(|dir| count(dir)).max();
+                let majority_dir = by_recency
+                    .iter()
+                    .map(|(_, dir)| *dir)
+                    .find(|dir| Some(count(dir)) == majority)
+                    .unwrap_or(last_focused_dir);
 
-    fn get_git_path_metadata(&self, path: PathBuf) -> Option<PathMetadata> {
-        if let Some(metadata) = self.path_metadata.get(&path) {
-            Some(metadata.clone())
-        } else {
-            if let Some(PermissionStatus::Granted) = self.permissions {
-                let mut context = BTreeMap::new();
-                context.insert(String::from("plugin"), String::from("tabula"));
-                context.insert(String::from("fn"), String::from("get_git_path_metadata"));
-                context.insert(String::from("path"), String::from(path.to_string_lossy()));
-                run_command_with_env_variables_and_cwd(
-                    &[
-                        "git",
-                        "rev-parse",
-                        "--path-format=absolute",
-                        "--show-toplevel",
-                        "--git-common-dir",
-                    ],
-                    BTreeMap::new(),
-                    path,
-                    context,
-                );
+                (majority_dir.clone(), TabSummary::path(""))
             }
+            MultiPaneStrategy::Repos => {
+                let mut labels: Vec<String> = Vec::new();
+                for dir in &distinct_dirs {
+                    // Directories outside git are labeled like repositories, by their name
+                    let path = describe_path(self, dir);
+                    let label = match (path.repo, dir.file_name()) {
+                        (Some(repo), _) => repo,
+                        (None, Some(name)) => name.to_string_lossy().into_owned(),
+                        (None, None) => path.display,
+                    };
+                    if !labels.contains(&label) {
+                        labels.push(label);
+                    }
+                }
 
-            None
+                (
+                    common_ancestor(&working_dirs),
+                    TabSummary::Text(labels.join("+")),
+                )
+            }
+            MultiPaneStrategy::FocusedPlusCount => (
+                last_focused_dir.clone(),
+                TabSummary::Path {
+                    path_suffix: "",
+                    annotation: format!(" +{}", distinct_dirs.len() - 1),
+                },
+            ),
         }
     }
 
-    fn organize(&self) {
-        'tab: for tab in &self.tabs {
-            let tab_position = tab.position;
+    fn parse_pane_status(&self, value: &str) -> Option<&StatusDefinition> {
+        self.statuses.get(value)
+    }
 
-            let panes: Vec<PaneInfo> = self
-                .panes
-                .panes
-                .get(&tab_position)
-                .cloned()
-                .unwrap_or_default()
-                .into_iter()
-                .filter(|p| !p.is_suppressed && !p.is_plugin)
-                .collect();
+    /// The status with the highest priority among `panes`.
+    fn tab_status(&self, panes: &[PaneInfo]) -> Option<&StatusDefinition> {
+        panes
+            .iter()
+            .filter_map(|pane| self.pane_statuses.get(&pane.id))
+            .filter_map(|status| self.statuses.get(&status.name))
+            .fold(
+                None,
+                |tab_status: Option<&StatusDefinition>, status| match tab_status {
+                    Some(tab_status) if tab_status.priority >= status.priority => Some(tab_status),
+                    _ => Some(status),
+                },
+            )
+    }
 
-            let working_dirs_in_tab: Vec<&PathBuf> = panes
-                .iter()
-                .filter_map(|p| self.pane_working_dirs.get(&p.id))
-                .collect();
+    fn parse_tab_name_template(&self) -> Template {
+        let Some(source) = self.userspace_configuration.get("tab_name_template") else {
+            return Template::default();
+        };
 
-            if working_dirs_in_tab.is_empty() {
-                continue;
+        match Template::parse(source) {
+            Ok(template) => template,
+            Err(err) => {
+                eprintln!("Invalid tab_name_template {source:?}: {err}, using the default");
+                Template::default()
             }
+        }
+    }
 
-            let mut tab_name = 'tab_name: {
-                let Some(first_working_dir) = working_dirs_in_tab.first().copied() else {
-                    // If there are no working dirs, skip this tab
-                    continue 'tab;
-                };
-
-                if working_dirs_in_tab.len() == 1 {
-                    break 'tab_name format_path(self, first_working_dir, "");
-                }
+    fn path_metadata_refresh_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.userspace_configuration
+                .get("path_metadata_refresh_interval")
+                .and_then(|interval| interval.parse::<u64>().ok())
+                .unwrap_or(10),
+        )
+    }
 
-                // If all working_dirs_in_tab are the same, use that as the tab name
-                if working_dirs_in_tab
-                    .iter()
-                    .all(|dir| *dir == first_working_dir)
-                {
-                    break 'tab_name format_path(self, first_working_dir, "/");
-                }
+    fn path_metadata_ttl(&self) -> Duration {
+        Duration::from_secs(
+            self.userspace_configuration
+                .get("path_metadata_ttl")
+                .and_then(|ttl| ttl.parse::<u64>().ok())
+                .unwrap_or(10),
+        )
+    }
 
-                // Get the common directory of all entries in working_dirs_in_tab
-                let mut common_dir = first_working_dir.clone();
+    fn non_git_path_ttl(&self) -> Duration {
+        Duration::from_secs(
+            self.userspace_configuration
+                .get("non_git_path_ttl")
+                .and_then(|ttl| ttl.parse::<u64>().ok())
+                .unwrap_or(60),
+        )
+    }
 
-                for dir in &working_dirs_in_tab {
-                    while !dir.starts_with(&common_dir) {
-                        if let Some(parent) = common_dir.parent() {
-                            common_dir = parent.to_path_buf();
-                        } else {
-                            break;
-                        }
-                    }
-                }
+    fn path_metadata_cache_size(&self) -> usize {
+        self.userspace_configuration
+            .get("path_metadata_cache_size")
+            .and_then(|cache_size| cache_size.parse::<usize>().ok())
+            .unwrap_or(256)
+    }
 
-                format!(
-                    "{} ({} panes)",
-                    format_path(self, &common_dir, "/*"),
-                    panes.len()
-                )
-            };
+    fn multi_pane_strategy(&self) -> MultiPaneStrategy {
+        match self
+            .userspace_configuration
+            .get("multi_pane_strategy")
+            .map(String::as_str)
+        {
+            Some("last_focused") => MultiPaneStrategy::LastFocused,
+            Some("majority") => MultiPaneStrategy::Majority,
+            Some("repos") => MultiPaneStrategy::Repos,
+            Some("focused_plus_count") => MultiPaneStrategy::FocusedPlusCount,
+            _ => MultiPaneStrategy::CommonAncestor,
+        }
+    }
 
-            if panes
+    fn ignored_commands(&self) -> Vec<String> {
+        match self.userspace_configuration.get("ignored_commands") {
+            Some(commands) => commands.split_whitespace().map(str::to_string).collect(),
+            None => DEFAULT_IGNORED_COMMANDS
                 .iter()
-                .any(|pane| self.pane_statuses.get(&pane.id) == Some(&PaneStatus::Waiting))
-            {
-                tab_name = format!("⏳{tab_name}");
-            }
+                .map(|command| (*command).to_string())
+                .collect(),
+        }
+    }
 
-            if self.tabs[tab_position].name == tab_name {
-                continue;
-            }
+    fn focus_rename_debounce(&self) -> Duration {
+        Duration::from_millis(
+            self.userspace_configuration
+                .get("focus_rename_debounce_ms")
+                .and_then(|debounce| debounce.parse::<u64>().ok())
+                .unwrap_or(300),
+        )
+    }
 
-            let Some(rename_target) = u64::try_from(tab.tab_id).ok() else {
-                continue;
-            };
+    /// The directory shown as `~`: the `home_dir` from the configuration if set, otherwise the
+    /// detected one.
+    fn home_dir(&self) -> Option<&str> {
+        self.userspace_configuration
+            .get("home_dir")
+            .or(self.environment.get("HOME"))
+            .map(|home_dir| home_dir.trim_end_matches('/'))
+            .filter(|home_dir| !home_dir.is_empty())
+    }
 
-            rename_tab_with_id(rename_target, tab_name);
+    fn max_tab_name_length(&self) -> usize {
+        self.userspace_configuration
+            .get("max_tab_name_length")
+            .and_then(|max_length| max_length.parse::<usize>().ok())
+            .unwrap_or(0)
+    }
+
+    fn path_abbreviation(&self) -> AbbreviationStrategy {
+        match self
+            .userspace_configuration
+            .get("path_abbreviation")
+            .map(String::as_str)
+        {
+            Some("middle") => AbbreviationStrategy::Middle,
+            Some("last_components") => AbbreviationStrategy::LastComponents(
+                self.userspace_configuration
+                    .get("path_abbreviation_components")
+                    .and_then(|count| count.parse::<usize>().ok())
+                    .unwrap_or(2),
+            ),
+            _ => AbbreviationStrategy::Fish,
+        }
+    }
+
+    fn manual_rename_policy(&self) -> ManualRenamePolicy {
+        match self
+            .userspace_configuration
+            .get("manual_rename_policy")
+            .map(String::as_str)
+        {
+            Some("override") => ManualRenamePolicy::Override,
+            _ => ManualRenamePolicy::Lock,
+        }
+    }
+
+    fn submodule_display(&self) -> SubmoduleDisplay {
+        match self
+            .userspace_configuration
+            .get("submodule_display")
+            .map(String::as_str)
+        {
+            Some("superproject_and_submodule") => SubmoduleDisplay::SuperprojectAndSubmodule,
+            _ => SubmoduleDisplay::SubmoduleOnly,
         }
     }
 
@@ -528,6 +2405,10 @@ impl State {
 mod tests {
     use super::*;
 
+    fn format_path(state: &State, path: &Path, path_suffix: &str) -> String {
+        format_path_description(&describe_path(state, path), path_suffix)
+    }
+
     fn state_with_home_dir(home_dir: &str) -> State {
         let mut state = State::default();
         state
@@ -558,22 +2439,368 @@ mod tests {
         );
     }
 
+    #[test]
+    fn prefers_configured_over_detected_home_dir() {
+        let mut state = State::default();
+
+        let mut context = BTreeMap::new();
+        context.insert(String::from("plugin"), String::from("tabula"));
+        context.insert(String::from("fn"), String::from("get_environment"));
+        context.insert(String::from("path"), String::from("/"));
+        state.handle_run_command_result(
+            Some(0),
+            b"/home/alice/\n\0".to_vec(),
+            Vec::new(),
+            &context,
+        );
+
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice/project"), ""),
+            "~/project"
+        );
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice2/project"), ""),
+            "/home/alice2/project"
+        );
+
+        state.userspace_configuration.insert(
+            String::from("home_dir"),
+            String::from("/home/alice/project"),
+        );
+
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice/project/src"), ""),
+            "~/src"
+        );
+    }
+
+    #[test]
+    fn formats_submodule_paths_with_superproject_name() {
+        let mut state = State::default();
+        let metadata = State::parse_git_path_metadata_output(
+            "/home/alice/parent/libs/sub\n\
+             /home/alice/parent/.git/modules/libs/sub\n\
+             /home/alice/parent\n",
+        )
+        .unwrap();
+        assert_eq!(metadata.repo_name, "sub");
+        state.path_metadata.insert(
+            PathBuf::from("/home/alice/parent/libs/sub/src"),
+            PathKind::Git(metadata),
+        );
+
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice/parent/libs/sub/src"), ""),
+            "sub/src"
+        );
+
+        state.userspace_configuration.insert(
+            String::from("submodule_display"),
+            String::from("superproject_and_submodule"),
+        );
+
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice/parent/libs/sub/src"), ""),
+            "parent⟩sub/src"
+        );
+    }
+
     #[test]
     fn formats_main_checkout_paths_with_repo_name() {
         let mut state = State::default();
         state.path_metadata.insert(
             PathBuf::from("/home/alice/git-project/src"),
-            PathMetadata {
+            PathKind::Git(PathMetadata {
                 git_worktree_root: PathBuf::from("/home/alice/git-project"),
                 repo_name: "git-project".to_string(),
                 worktree_name: "git-project".to_string(),
-            },
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
+        );
+
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice/git-project/src"), ""),
+            "git-project/src"
+        );
+    }
+
+    #[test]
+    fn applies_path_rules_before_shortening_paths() {
+        let mut state = state_with_home_dir("/home/alice");
+        state.userspace_configuration.insert(
+            String::from("path_rules"),
+            String::from("glob \"~/monorepo/services/*\" \"svc:{1}\""),
+        );
+        state.path_rules = PathRules::from_configuration(&state.userspace_configuration);
+        state.path_metadata.insert(
+            PathBuf::from("/home/alice/monorepo/services/auth/src"),
+            PathKind::Git(PathMetadata {
+                git_worktree_root: PathBuf::from("/home/alice/monorepo"),
+                repo_name: "monorepo".to_string(),
+                worktree_name: "monorepo".to_string(),
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
+        );
+
+        let description =
+            describe_path(&state, Path::new("/home/alice/monorepo/services/auth/src"));
+        assert_eq!(description.display, "svc:auth/src");
+        assert_eq!(description.repo, Some("monorepo".to_string()));
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice/monorepo/docs"), ""),
+            "~/monorepo/docs"
+        );
+    }
+
+    #[test]
+    fn abbreviates_tab_names_to_fit_the_max_length() {
+        let mut state = state_with_home_dir("/home/alice");
+        state.pane_working_dirs.insert(
+            1,
+            PathBuf::from("/home/alice/projects/tabula/src/components"),
+        );
+
+        let fitted_tab_name = |state: &mut State, max_length: &str, strategy: &str| {
+            state
+                .userspace_configuration
+                .insert(String::from("max_tab_name_length"), max_length.to_string());
+            state
+                .userspace_configuration
+                .insert(String::from("path_abbreviation"), strategy.to_string());
+            let context = state.tab_name_context(&[terminal_pane(1)]).unwrap();
+            state.fitted_tab_name(1, context)
+        };
+
+        assert_eq!(
+            fitted_tab_name(&mut state, "0", "fish"),
+            "~/projects/tabula/src/components"
+        );
+        assert_eq!(
+            fitted_tab_name(&mut state, "20", "fish"),
+            "~/p/t/src/components"
+        );
+        assert_eq!(fitted_tab_name(&mut state, "10", "fish"), "~/p/t/s/c…");
+        assert_eq!(
+            fitted_tab_name(&mut state, "20", "last_components"),
+            "…/src/components"
+        );
+        assert_eq!(fitted_tab_name(&mut state, "10", "middle"), "~/pro…ents");
+    }
+
+    #[test]
+    fn summarizes_panes_in_different_directories() {
+        let mut state = state_with_home_dir("/home/alice");
+        state.path_metadata.insert(
+            PathBuf::from("/home/alice/api/src"),
+            PathKind::Git(PathMetadata {
+                git_worktree_root: PathBuf::from("/home/alice/api"),
+                repo_name: "api".to_string(),
+                worktree_name: "api".to_string(),
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
+        );
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/web"));
+        state
+            .pane_working_dirs
+            .insert(2, PathBuf::from("/home/alice/api/src"));
+        state
+            .pane_working_dirs
+            .insert(3, PathBuf::from("/home/alice/web"));
+        state.tabs = vec![TabInfo::default()];
+        state.panes.panes = std::iter::once((
+            0,
+            vec![
+                terminal_pane(1),
+                PaneInfo {
+                    is_focused: true,
+                    ..terminal_pane(2)
+                },
+                terminal_pane(3),
+            ],
+        ))
+        .collect();
+        state.track_focused_panes(Instant::now());
+
+        let panes = state.panes.panes[&0].clone();
+        let mut tab_name = |strategy: &str| {
+            state
+                .userspace_configuration
+                .insert(String::from("multi_pane_strategy"), strategy.to_string());
+            render_tab_name(&state, &panes).unwrap()
+        };
+
+        assert_eq!(tab_name("common_ancestor"), "~/* (3 panes)");
+        assert_eq!(tab_name("last_focused"), "api/src");
+        assert_eq!(tab_name("majority"), "~/web");
+        assert_eq!(tab_name("repos"), "web+api");
+        assert_eq!(tab_name("focused_plus_count"), "api/src +1");
+
+        // Placeholders refer to the directory the strategy chose
+        state.tab_name_template = Template::parse("{path}{multi_suffix}").unwrap();
+        let mut tab_name = |strategy: &str| {
+            state
+                .userspace_configuration
+                .insert(String::from("multi_pane_strategy"), strategy.to_string());
+            render_tab_name(&state, &panes).unwrap()
+        };
+
+        assert_eq!(tab_name("common_ancestor"), "~/*");
+        assert_eq!(tab_name("last_focused"), "api/src");
+        assert_eq!(tab_name("repos"), "~/*");
+    }
+
+    #[test]
+    fn debounces_renames_when_the_focus_moves() {
+        let mut state = state_with_home_dir("/home/alice");
+        state.userspace_configuration.insert(
+            String::from("multi_pane_strategy"),
+            String::from("last_focused"),
+        );
+        state.tabs = vec![TabInfo::default()];
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/api"));
+        state
+            .pane_working_dirs
+            .insert(2, PathBuf::from("/home/alice/web"));
+
+        let focus = |state: &mut State, pane_id: u32, now: Instant| {
+            state.panes.panes = std::iter::once((
+                0,
+                [1, 2]
+                    .iter()
+                    .map(|&id| PaneInfo {
+                        is_focused: id == pane_id,
+                        ..terminal_pane(id)
+                    })
+                    .collect(),
+            ))
+            .collect();
+            state.handle_focus_change(now);
+        };
+
+        let now = Instant::now();
+        focus(&mut state, 1, now);
+        focus(&mut state, 2, now + Duration::from_millis(100));
+        assert_eq!(
+            state.next_focus_rename,
+            Some(now + Duration::from_millis(400))
+        );
+
+        // Renames for other reasons keep using the focus from before the debounce
+        state.organize();
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("~/api"));
+
+        focus(&mut state, 2, now + Duration::from_millis(200));
+        state.handle_timer(now + Duration::from_millis(300));
+        assert!(state.next_focus_rename.is_some());
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("~/api"));
+
+        state.handle_timer(now + Duration::from_millis(400));
+        assert_eq!(state.next_focus_rename, None);
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("~/web"));
+    }
+
+    #[test]
+    fn shows_the_command_running_in_a_pane() {
+        let ignored_commands: Vec<String> = DEFAULT_IGNORED_COMMANDS
+            .iter()
+            .map(|command| (*command).to_string())
+            .collect();
+        let command = |terminal_command: Option<&str>, title: &str| {
+            let pane = PaneInfo {
+                terminal_command: terminal_command.map(str::to_string),
+                title: title.to_string(),
+                ..terminal_pane(1)
+            };
+            pane_command(&pane, &ignored_commands)
+        };
+
+        assert_eq!(
+            command(Some("/usr/bin/cargo test --workspace"), "Pane #1"),
+            Some("cargo test --workspace".to_string())
+        );
+        assert_eq!(
+            command(None, "nvim src/main.rs"),
+            Some("nvim src/main.rs".to_string())
         );
+        assert_eq!(command(None, "htop"), Some("htop".to_string()));
+        assert_eq!(command(None, "-zsh"), None);
+        assert_eq!(command(Some("/bin/bash"), "bash"), None);
+        assert_eq!(command(None, "~/projects/tabula"), None);
+        assert_eq!(command(None, "Pane #2"), None);
+        assert_eq!(command(None, "alice@host: ~/project"), None);
+        assert_eq!(command(None, "alice@host:~/project"), None);
+        assert_eq!(command(None, "host: /srv"), None);
+        assert_eq!(
+            command(None, "ssh alice@host"),
+            Some("ssh alice@host".to_string())
+        );
+    }
+
+    #[test]
+    fn defers_command_renames_when_the_focus_moves() {
+        let mut state = State {
+            tab_name_template: Template::parse("{command}").unwrap(),
+            tabs: vec![TabInfo::default()],
+            ..state_with_home_dir("/home/alice")
+        };
+        state.userspace_configuration.insert(
+            String::from("multi_pane_strategy"),
+            String::from("last_focused"),
+        );
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/api"));
+        state
+            .pane_working_dirs
+            .insert(2, PathBuf::from("/home/alice/web"));
+
+        let update = |state: &mut State, focused: u32, title_2: &str| {
+            let panes = [(1, "nvim"), (2, title_2)]
+                .iter()
+                .map(|&(id, title)| PaneInfo {
+                    is_focused: id == focused,
+                    title: title.to_string(),
+                    ..terminal_pane(id)
+                })
+                .collect();
+            state.update(Event::PaneUpdate(PaneManifest {
+                panes: std::iter::once((0, panes)).collect(),
+            }));
+        };
+
+        update(&mut state, 1, "htop");
+        state.organize();
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("nvim"));
+
+        // The focus moved, but only the debounce renames the tab
+        update(&mut state, 2, "htop");
+        assert!(state.next_focus_rename.is_some());
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("nvim"));
 
-        assert_eq!(
-            format_path(&state, Path::new("/home/alice/git-project/src"), ""),
-            "git-project/src"
-        );
+        // A title change of a pane the name doesn't depend on doesn't rename it either
+        update(&mut state, 2, "cargo test");
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("nvim"));
+
+        let due = state.next_focus_rename.unwrap();
+        state.handle_timer(due);
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("cargo test"));
+
+        // Once the focus has settled, title changes rename the tab right away
+        update(&mut state, 2, "lazygit");
+        assert_eq!(applied_tab_name(&state, 0).as_deref(), Some("lazygit"));
     }
 
     #[test]
@@ -581,11 +2808,15 @@ mod tests {
         let mut state = state_with_worktree_config("repo_and_worktree", 10);
         state.path_metadata.insert(
             PathBuf::from("/home/alice/git-project-worktree/src"),
-            PathMetadata {
+            PathKind::Git(PathMetadata {
                 git_worktree_root: PathBuf::from("/home/alice/git-project-worktree"),
                 repo_name: "git-project".to_string(),
                 worktree_name: "git-project-worktree".to_string(),
-            },
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
         );
 
         assert_eq!(
@@ -598,16 +2829,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn truncates_wide_worktree_names_by_display_width() {
+        let mut state = state_with_worktree_config("repo_and_worktree", 6);
+        state.path_metadata.insert(
+            PathBuf::from("/home/alice/プロジェクト-機能"),
+            PathKind::Git(PathMetadata {
+                git_worktree_root: PathBuf::from("/home/alice/プロジェクト-機能"),
+                repo_name: "プロジェクト".to_string(),
+                worktree_name: "プロジェクト-機能".to_string(),
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
+        );
+
+        assert_eq!(
+            format_path(&state, Path::new("/home/alice/プロジェクト-機能"), ""),
+            "プロジェクト (🌲 プロジ...)"
+        );
+    }
+
     #[test]
     fn formats_worktree_only_paths_without_emoji() {
         let mut state = state_with_worktree_config("worktree_only", 10);
         state.path_metadata.insert(
             PathBuf::from("/home/alice/git-project-worktree/src"),
-            PathMetadata {
+            PathKind::Git(PathMetadata {
                 git_worktree_root: PathBuf::from("/home/alice/git-project-worktree"),
                 repo_name: "git-project".to_string(),
                 worktree_name: "git-project-worktree".to_string(),
-            },
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
         );
 
         assert_eq!(
@@ -625,11 +2882,15 @@ mod tests {
         let mut state = state_with_worktree_config("repo_and_worktree", 10);
         state.path_metadata.insert(
             PathBuf::from("/home/alice/feature-x/src"),
-            PathMetadata {
+            PathKind::Git(PathMetadata {
                 git_worktree_root: PathBuf::from("/home/alice/feature-x"),
                 repo_name: "git-project".to_string(),
                 worktree_name: "feature-x".to_string(),
-            },
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
         );
 
         assert_eq!(
@@ -643,11 +2904,15 @@ mod tests {
         let mut state = state_with_worktree_config("repo_and_worktree", 10);
         state.path_metadata.insert(
             PathBuf::from("/home/alice/git-project-worktree/src"),
-            PathMetadata {
+            PathKind::Git(PathMetadata {
                 git_worktree_root: PathBuf::from("/home/alice/git-project-worktree"),
                 repo_name: "git-project".to_string(),
                 worktree_name: "git-project-worktree".to_string(),
-            },
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
         );
 
         assert_eq!(
@@ -660,10 +2925,726 @@ mod tests {
         );
     }
 
+    fn terminal_pane(id: u32) -> PaneInfo {
+        PaneInfo {
+            id,
+            ..PaneInfo::default()
+        }
+    }
+
+    fn render_tab_name(state: &State, panes: &[PaneInfo]) -> Option<String> {
+        let context = state.tab_name_context(panes)?;
+        Some(
+            state
+                .tab_name_template
+                .render(&|placeholder| context.value(placeholder)),
+        )
+    }
+
+    /// The name `organize` last gave the tab.
+    fn applied_tab_name(state: &State, tab_id: usize) -> Option<String> {
+        state
+            .applied_tab_names
+            .borrow()
+            .get(&tab_id)
+            .map(|applied| applied.name.clone())
+    }
+
+    #[test]
+    fn renders_default_tab_names_for_differing_working_dirs() {
+        let mut state = state_with_home_dir("/home/alice");
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/project/src"));
+        state
+            .pane_working_dirs
+            .insert(2, PathBuf::from("/home/alice/project/tests"));
+        state
+            .pane_statuses
+            .insert(2, PaneStatus::new("waiting", Instant::now()));
+
+        assert_eq!(
+            render_tab_name(&state, &[terminal_pane(1), terminal_pane(2)]).as_deref(),
+            Some("⏳~/project/* (2 panes)")
+        );
+    }
+
+    #[test]
+    fn renders_tab_names_from_template() {
+        let mut state = State {
+            tab_name_template: Template::parse(
+                "{status}{repo}{?/{rel_path}}{multi_suffix}{? [{worktree}]}",
+            )
+            .unwrap(),
+            ..State::default()
+        };
+        state.path_metadata.insert(
+            PathBuf::from("/home/alice/git-project"),
+            PathKind::Git(PathMetadata {
+                git_worktree_root: PathBuf::from("/home/alice/git-project"),
+                repo_name: "git-project".to_string(),
+                worktree_name: "git-project".to_string(),
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
+        );
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/git-project"));
+        state
+            .pane_working_dirs
+            .insert(2, PathBuf::from("/home/alice/git-project"));
+
+        assert_eq!(
+            render_tab_name(&state, &[terminal_pane(1), terminal_pane(2)]).as_deref(),
+            Some("git-project/")
+        );
+    }
+
+    #[test]
+    fn parses_git_head_state_output() {
+        assert_eq!(
+            parse_git_head_state_output("branch main\ncommit 1a2b3c4\n"),
+            GitHead {
+                branch: Some("main".to_string()),
+                commit: Some("1a2b3c4".to_string()),
+                operation: None,
+            }
+        );
+        assert_eq!(
+            parse_git_head_state_output("commit 1a2b3c4\noperation rebase\n"),
+            GitHead {
+                branch: None,
+                commit: Some("1a2b3c4".to_string()),
+                operation: Some(GitOperation::Rebase),
+            }
+        );
+    }
+
+    #[test]
+    fn renders_detached_head_and_operation_from_template() {
+        let mut state = State {
+            tab_name_template: Template::parse("{repo} [{head}]{? ({operation})}").unwrap(),
+            ..State::default()
+        };
+        state.path_metadata.insert(
+            PathBuf::from("/home/alice/git-project"),
+            PathKind::Git(PathMetadata {
+                git_worktree_root: PathBuf::from("/home/alice/git-project"),
+                repo_name: "git-project".to_string(),
+                worktree_name: "git-project".to_string(),
+                head: GitHead {
+                    branch: None,
+                    commit: Some("1a2b3c4".to_string()),
+                    operation: Some(GitOperation::Rebase),
+                },
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
+        );
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/git-project"));
+
+        assert_eq!(
+            render_tab_name(&state, &[terminal_pane(1)]).as_deref(),
+            Some("git-project [1a2b3c4] (rebase)")
+        );
+    }
+
+    #[test]
+    fn renders_workspace_members_from_template() {
+        let mut state = State {
+            tab_name_template: Template::parse("{repo}{?:{package}}{?/{package_path}}").unwrap(),
+            ..State::default()
+        };
+        let path = PathBuf::from("/home/alice/monorepo/crates/foo/src");
+        state.path_metadata.insert(
+            path.clone(),
+            PathKind::Git(PathMetadata {
+                git_worktree_root: PathBuf::from("/home/alice/monorepo"),
+                repo_name: "monorepo".to_string(),
+                worktree_name: "monorepo".to_string(),
+                head: GitHead::default(),
+                status: None,
+                package: None,
+                superproject_root: None,
+            }),
+        );
+        state.pane_working_dirs.insert(1, path.clone());
+
+        assert_eq!(
+            render_tab_name(&state, &[terminal_pane(1)]).as_deref(),
+            Some("monorepo")
+        );
+
+        let mut context = BTreeMap::new();
+        context.insert(String::from("plugin"), String::from("tabula"));
+        context.insert(String::from("fn"), String::from("get_package"));
+        context.insert(String::from("path"), path.to_string_lossy().to_string());
+        state.handle_run_command_result(
+            Some(0),
+            b"/home/alice/monorepo/crates/foo\nfoo\n".to_vec(),
+            Vec::new(),
+            &context,
+        );
+
+        assert_eq!(
+            render_tab_name(&state, &[terminal_pane(1)]).as_deref(),
+            Some("monorepo:foo/src")
+        );
+    }
+
+    #[test]
+    fn parses_git_status_output() {
+        let status = parse_git_status_output(
+            "# branch.oid 1a2b3c4\n\
+             # branch.head main\n\
+             # branch.upstream origin/main\n\
+             # branch.ab +2 -1\n\
+             # stash 3\n\
+             1 .M N... 100644 100644 100644 0000000 0000000 src/main.rs\n\
+             1 A. N... 000000 100644 100644 0000000 0000000 src/template.rs\n\
+             ? notes.txt\n",
+        );
+
+        assert_eq!(
+            status,
+            GitStatus {
+                staged: 1,
+                modified: 1,
+                untracked: 1,
+                conflicted: 0,
+                ahead: 2,
+                behind: 1,
+                stashes: 3,
+            }
+        );
+        assert_eq!(status.glyphs(), "+!?⇡2⇣1$3");
+        assert_eq!(GitStatus::default().glyphs(), "");
+    }
+
+    #[test]
+    fn drops_expired_path_metadata_that_is_no_longer_used() {
+        let mut state = State::default();
+        let metadata = PathKind::Git(PathMetadata {
+            git_worktree_root: PathBuf::from("/home/alice/git-project"),
+            repo_name: "git-project".to_string(),
+            worktree_name: "git-project".to_string(),
+            head: GitHead::default(),
+            status: None,
+            package: None,
+            superproject_root: None,
+        });
+        state
+            .path_metadata
+            .insert(PathBuf::from("/home/alice/git-project"), metadata.clone());
+        state
+            .path_metadata
+            .insert(PathBuf::from("/home/alice/git-project/src"), metadata);
+        state
+            .path_metadata
+            .insert(PathBuf::from("/tmp"), PathKind::NotGit);
+        std::thread::sleep(Duration::from_millis(1));
+        state
+            .path_metadata
+            .get(Path::new("/home/alice/git-project"));
+
+        state.refresh_path_metadata(Instant::now() + state.path_metadata_ttl());
+
+        assert_eq!(
+            state.path_metadata.paths(),
+            vec![
+                PathBuf::from("/home/alice/git-project"),
+                PathBuf::from("/tmp")
+            ]
+        );
+
+        state.refresh_path_metadata(Instant::now() + state.non_git_path_ttl());
+
+        assert_eq!(
+            state.path_metadata.paths(),
+            vec![PathBuf::from("/home/alice/git-project")]
+        );
+    }
+
+    #[test]
+    fn runs_one_git_command_per_path_and_remembers_non_git_paths() {
+        let mut state = State {
+            permissions: Some(PermissionStatus::Granted),
+            ..State::default()
+        };
+        let path = PathBuf::from("/tmp");
+        let key = (String::from("get_git_path_metadata"), path.clone());
+
+        state.get_git_path_metadata(path.clone());
+        state.get_git_path_metadata(path.clone());
+
+        assert_eq!(
+            state
+                .pending_commands
+                .borrow()
+                .get(&key)
+                .map(|pending| pending.attempts),
+            Some(1)
+        );
+
+        let mut context = BTreeMap::new();
+        context.insert(String::from("plugin"), String::from("tabula"));
+        context.insert(String::from("fn"), String::from("get_git_path_metadata"));
+        context.insert(String::from("path"), String::from("/tmp"));
+        state.handle_run_command_result(Some(128), Vec::new(), Vec::new(), &context);
+
+        // Outside of git, other kinds of projects are looked for
+        assert_eq!(
+            state
+                .pending_commands
+                .borrow()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![(String::from("detect_project"), path.clone())]
+        );
+        assert!(matches!(
+            state.path_metadata.get(&path),
+            Some(PathKind::NotGit)
+        ));
+
+        context.insert(String::from("fn"), String::from("detect_project"));
+        state.handle_run_command_result(Some(1), Vec::new(), Vec::new(), &context);
+        state.get_git_path_metadata(path);
+
+        assert!(state.pending_commands.borrow().is_empty());
+    }
+
+    #[test]
+    fn refetches_non_git_paths_when_a_pane_changes_into_them() {
+        let mut state = State {
+            permissions: Some(PermissionStatus::Granted),
+            ..State::default()
+        };
+        let path = PathBuf::from("/tmp/new-repo");
+        state.path_metadata.insert(path.clone(), PathKind::NotGit);
+
+        state.update(Event::CwdChanged(
+            PaneId::Terminal(1),
+            path.clone(),
+            Vec::new(),
+        ));
+
+        assert!(state
+            .pending_commands
+            .borrow()
+            .contains_key(&(String::from("get_git_path_metadata"), path)));
+    }
+
+    #[test]
+    fn names_tabs_after_projects_outside_of_git() {
+        let mut state = State::default();
+        let path = PathBuf::from("/home/alice/jj-project/src");
+
+        let mut context = BTreeMap::new();
+        context.insert(String::from("plugin"), String::from("tabula"));
+        context.insert(String::from("fn"), String::from("get_git_path_metadata"));
+        context.insert(String::from("path"), path.to_string_lossy().to_string());
+        state.handle_run_command_result(Some(128), Vec::new(), Vec::new(), &context);
+
+        context.insert(String::from("fn"), String::from("detect_project"));
+        state.handle_run_command_result(
+            Some(0),
+            b"/home/alice/jj-project\n".to_vec(),
+            Vec::new(),
+            &context,
+        );
+
+        assert_eq!(format_path(&state, &path, ""), "jj-project/src");
+
+        // A project stays known while it is detected again
+        context.insert(String::from("fn"), String::from("get_git_path_metadata"));
+        state.handle_run_command_result(Some(128), Vec::new(), Vec::new(), &context);
+        assert_eq!(format_path(&state, &path, ""), "jj-project/src");
+
+        context.insert(String::from("fn"), String::from("detect_project"));
+        state.handle_run_command_result(Some(0), b"/srv\n".to_vec(), Vec::new(), &context);
+        assert_eq!(format_path(&state, &path, ""), "jj-project/src");
+
+        state.handle_run_command_result(Some(1), Vec::new(), Vec::new(), &context);
+        assert_eq!(format_path(&state, &path, ""), "/home/alice/jj-project/src");
+    }
+
+    #[test]
+    fn applies_on_focus_transitions_to_the_focused_pane() {
+        let mut configuration = BTreeMap::new();
+        configuration.insert(
+            String::from("statuses"),
+            String::from("deploying on_focus=\"success\" focus_delay=5"),
+        );
+        let mut state = State {
+            statuses: StatusRegistry::from_configuration(&configuration),
+            tabs: vec![TabInfo {
+                active: true,
+                ..TabInfo::default()
+            }],
+            ..State::default()
+        };
+        let now = Instant::now();
+        state
+            .pane_statuses
+            .insert(1, PaneStatus::new("attention", now));
+        state
+            .pane_statuses
+            .insert(2, PaneStatus::new("deploying", now));
+
+        let focus = |state: &mut State, pane_id: u32, now: Instant| {
+            let panes = [1, 2]
+                .iter()
+                .map(|&id| PaneInfo {
+                    is_focused: id == pane_id,
+                    ..terminal_pane(id)
+                })
+                .collect();
+            state.panes.panes = std::iter::once((0, panes)).collect();
+            state.handle_focus_change(now);
+        };
+        let status_name = |state: &State, pane_id: u32| {
+            state
+                .pane_statuses
+                .get(&pane_id)
+                .map(|status| status.name.clone())
+        };
+
+        focus(&mut state, 1, now);
+        assert_eq!(status_name(&state, 1), None);
+
+        focus(&mut state, 2, now);
+        assert_eq!(status_name(&state, 2), Some("deploying".to_string()));
+
+        state.handle_timer(now + Duration::from_secs(5));
+        assert_eq!(status_name(&state, 2), Some("success".to_string()));
+    }
+
+    #[test]
+    fn expires_pane_statuses_set_with_a_ttl() {
+        let mut state = State::default();
+        let set_status = |state: &mut State, pane_id: u32, on_expire: Option<&str>| {
+            state.handle_pipe_command(PipeCommand::SetStatus {
+                pane_id,
+                status: "running".to_string(),
+                ttl: Some(Duration::from_mins(10)),
+                on_expire: on_expire.map(str::to_string),
+            })
+        };
+
+        assert_eq!(set_status(&mut state, 1, None), Ok(()));
+        assert_eq!(set_status(&mut state, 2, Some("error")), Ok(()));
+        assert_eq!(
+            set_status(&mut state, 3, Some("busy")),
+            Err("Unknown pane status: busy".to_string())
+        );
+
+        state.handle_timer(Instant::now() + Duration::from_secs(599));
+        assert_eq!(state.pane_statuses.len(), 2);
+
+        state.handle_timer(Instant::now() + Duration::from_mins(10));
+        assert_eq!(state.pane_statuses.get(&1), None);
+        assert_eq!(
+            state
+                .pane_statuses
+                .get(&2)
+                .map(|status| status.name.as_str()),
+            Some("error")
+        );
+        assert_eq!(state.next_status_expiry(), None);
+    }
+
+    #[test]
+    fn restores_persisted_state_without_overriding_newer_state() {
+        let mut state = State::default();
+        let now = Instant::now();
+        state
+            .pane_statuses
+            .insert(1, PaneStatus::new("running", now));
+
+        let persisted: PersistedState = serde_json::from_str(
+            r#"{
+                "pane_statuses": {
+                    "1": { "name": "waiting" },
+                    "2": { "name": "waiting" },
+                    "3": { "name": "busy" },
+                    "4": { "name": "waiting" }
+                },
+                "pane_working_dirs": { "2": "/home/alice/project", "5": "/tmp" }
+            }"#,
+        )
+        .unwrap();
+        state.restore_state(persisted, now, SystemTime::now());
+
+        let status_names: Vec<(u32, &str)> = state
+            .pane_statuses
+            .iter()
+            .map(|(pane_id, status)| (*pane_id, status.name.as_str()))
+            .collect();
+        assert_eq!(
+            status_names,
+            vec![(1, "running"), (2, "waiting"), (4, "waiting")]
+        );
+        assert_eq!(
+            state.pane_working_dirs.get(&2),
+            Some(&PathBuf::from("/home/alice/project"))
+        );
+
+        // Panes 4 and 5 were closed, or belonged to an earlier session of the same name
+        state.panes.panes = std::iter::once((0, vec![terminal_pane(2)])).collect();
+        state.forget_closed_restored_panes();
+
+        assert_eq!(
+            state.pane_statuses.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            state.pane_working_dirs.keys().copied().collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert!(state.restored_pane_ids.is_empty());
+    }
+
+    #[test]
+    fn locks_tabs_renamed_by_the_user() {
+        let mut state = state_with_home_dir("/home/alice");
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/project"));
+        state.panes.panes = std::iter::once((0, vec![terminal_pane(1)])).collect();
+
+        let rename_tab = |state: &mut State, name: &str| {
+            state.tabs = vec![TabInfo {
+                tab_id: 7,
+                name: name.to_string(),
+                active: true,
+                ..TabInfo::default()
+            }];
+            state.detect_manual_renames();
+        };
+
+        rename_tab(&mut state, "Tab #1");
+        state.organize();
+        assert_eq!(
+            state
+                .applied_tab_names
+                .borrow()
+                .get(&7)
+                .map(|applied| applied.name.clone()),
+            Some("~/project".to_string())
+        );
+
+        // The rename hasn't been reported yet
+        rename_tab(&mut state, "Tab #1");
+        rename_tab(&mut state, "~/project");
+        assert!(state.locked_tabs.is_empty());
+
+        rename_tab(&mut state, "notes");
+        assert!(state.locked_tabs.contains_key(&7));
+
+        // Locked tabs keep showing the status of their panes
+        state
+            .pane_statuses
+            .insert(1, PaneStatus::new("waiting", Instant::now()));
+        state.organize();
+        assert_eq!(applied_tab_name(&state, 7), Some("⏳notes".to_string()));
+        rename_tab(&mut state, "⏳notes");
+        assert_eq!(state.locked_tabs.get(&7), Some(&"notes".to_string()));
+
+        rename_tab(&mut state, "⏳todo");
+        assert_eq!(state.locked_tabs.get(&7), Some(&"todo".to_string()));
+
+        assert_eq!(
+            state.handle_pipe_command(PipeCommand::Unlock { tab_id: None }),
+            Ok(())
+        );
+        assert!(state.locked_tabs.is_empty());
+
+        rename_tab(&mut state, "⏳~/project");
+        assert_eq!(
+            state.handle_pipe_command(PipeCommand::Lock { tab_id: None }),
+            Ok(())
+        );
+        assert_eq!(state.locked_tabs.get(&7), Some(&"~/project".to_string()));
+        assert_eq!(
+            state.handle_pipe_command(PipeCommand::Unlock { tab_id: None }),
+            Ok(())
+        );
+        state.pane_statuses.remove(&1);
+        assert_eq!(
+            state.handle_pipe_command(PipeCommand::Lock { tab_id: Some(8) }),
+            Err("Unknown tab id: 8".to_string())
+        );
+
+        state.userspace_configuration.insert(
+            String::from("manual_rename_policy"),
+            String::from("override"),
+        );
+        rename_tab(&mut state, "~/project");
+        rename_tab(&mut state, "notes");
+        assert!(state.locked_tabs.is_empty());
+    }
+
+    #[test]
+    fn restores_locks_and_templates_of_tabs_that_kept_their_name() {
+        let mut state = state_with_home_dir("/home/alice");
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/project"));
+        state.panes.panes = (0..5)
+            .map(|position| (position, vec![terminal_pane(1)]))
+            .collect();
+
+        let persisted: PersistedState = serde_json::from_str(
+            r#"{
+                "locked_tabs": {
+                    "7": { "label": "notes", "name": "notes" },
+                    "8": { "label": "Tab #2", "name": "Tab #2" },
+                    "9": { "label": "~/project", "name": "~/project" },
+                    "10": { "label": "logs", "name": "logs" }
+                },
+                "tab_templates": {
+                    "11": { "template": "api: {auto}", "name": "api: ~/project" },
+                    "12": { "template": "api: {auto}", "name": "api: ~/project" }
+                }
+            }"#,
+        )
+        .unwrap();
+        state.restore_state(persisted, Instant::now(), SystemTime::now());
+        assert_eq!(state.locked_tabs.len(), 4);
+        assert_eq!(state.tab_templates.len(), 2);
+
+        state.tabs = [
+            (7, "notes"),
+            (8, "Tab #1"),
+            (9, "~/project"),
+            (11, "api: ~/project"),
+            (12, "Tab #5"),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(position, (tab_id, name))| TabInfo {
+            tab_id: *tab_id,
+            position,
+            name: (*name).to_string(),
+            ..TabInfo::default()
+        })
+        .collect();
+        state.confirm_restored_tabs();
+
+        assert_eq!(
+            state.locked_tabs.keys().copied().collect::<Vec<_>>(),
+            vec![7]
+        );
+        assert_eq!(
+            state.tab_templates.keys().copied().collect::<Vec<_>>(),
+            vec![11]
+        );
+        assert!(state.restored_tab_names.is_empty());
+    }
+
+    #[test]
+    fn names_tabs_from_templates_given_as_tab_names() {
+        let mut state = state_with_home_dir("/home/alice");
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/project"));
+        state.panes.panes = std::iter::once((0, vec![terminal_pane(1)])).collect();
+
+        let rename_tab = |state: &mut State, name: &str| {
+            state.tabs = vec![TabInfo {
+                tab_id: 7,
+                name: name.to_string(),
+                ..TabInfo::default()
+            }];
+            state.detect_manual_renames()
+        };
+        let tab_name = |state: &State| {
+            let context = state.tab_name_context(&[terminal_pane(1)]).unwrap();
+            state.tab_name(7, &context)
+        };
+
+        assert!(rename_tab(&mut state, "api: {auto} {{{pane_count}}}"));
+        assert_eq!(tab_name(&state), "api: ~/project {1}");
+
+        state.organize();
+        assert!(!rename_tab(&mut state, "api: {auto} {{{pane_count}}}"));
+        assert!(!rename_tab(&mut state, "api: ~/project {1}"));
+        assert!(state.tab_templates.contains_key(&7));
+
+        assert!(!rename_tab(&mut state, "notes"));
+        assert!(state.tab_templates.is_empty());
+        assert!(state.locked_tabs.contains_key(&7));
+    }
+
+    #[test]
+    fn shows_the_highest_priority_status_of_a_tab() {
+        let mut state = State::default();
+        state
+            .pane_statuses
+            .insert(1, PaneStatus::new("running", Instant::now()));
+        state
+            .pane_statuses
+            .insert(2, PaneStatus::new("error", Instant::now()));
+        state
+            .pane_statuses
+            .insert(3, PaneStatus::new("waiting", Instant::now()));
+        let panes = [terminal_pane(1), terminal_pane(2), terminal_pane(3)];
+
+        let tab_status = |state: &State, panes: &[PaneInfo]| {
+            state
+                .tab_status(panes)
+                .map(|status| (status.name.clone(), status.glyph.clone()))
+        };
+
+        assert_eq!(
+            tab_status(&state, &panes),
+            Some(("error".to_string(), "❌".to_string()))
+        );
+        assert_eq!(
+            tab_status(&state, &panes[..1]),
+            Some(("running".to_string(), "🔄".to_string()))
+        );
+
+        state
+            .userspace_configuration
+            .insert(String::from("status_priority_waiting"), String::from("99"));
+        state
+            .userspace_configuration
+            .insert(String::from("status_glyph_waiting"), String::from("W "));
+        state.statuses = StatusRegistry::from_configuration(&state.userspace_configuration);
+
+        assert_eq!(
+            tab_status(&state, &panes),
+            Some(("waiting".to_string(), "W ".to_string()))
+        );
+    }
+
     #[test]
     fn parses_pane_status_values() {
-        assert_eq!(parse_pane_status("waiting"), Some(PaneStatus::Waiting));
-        assert_eq!(parse_pane_status("none"), Some(PaneStatus::None));
-        assert_eq!(parse_pane_status("busy"), None);
+        let mut state = State::default();
+        state.userspace_configuration.insert(
+            String::from("statuses"),
+            String::from("deploying glyph=\"🚀\""),
+        );
+        state.statuses = StatusRegistry::from_configuration(&state.userspace_configuration);
+
+        let parse = |value| {
+            state
+                .parse_pane_status(value)
+                .map(|status| status.name.as_str())
+        };
+
+        assert_eq!(parse("waiting"), Some("waiting"));
+        assert_eq!(parse("none"), Some("none"));
+        assert_eq!(parse("done-unseen"), Some("done-unseen"));
+        assert_eq!(parse("deploying"), Some("deploying"));
+        assert_eq!(parse("busy"), None);
     }
 }
@@ -0,0 +1,156 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const DEFAULT_CAPACITY: usize = 256;
+
+struct Entry<V> {
+    value: V,
+    /// When the value was last (re)fetched
+    fetched_at: Instant,
+    /// When the value was last read, used to evict the least recently used entries
+    last_used: Cell<Instant>,
+}
+
+/// Values keyed by path that remember how old they are and when they were last read.
+pub struct PathCache<V> {
+    entries: BTreeMap<PathBuf, Entry<V>>,
+    capacity: usize,
+}
+
+impl<V> Default for PathCache<V> {
+    fn default() -> Self {
+        PathCache {
+            entries: BTreeMap::new(),
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+impl<V> PathCache<V> {
+    /// Sets the maximum number of entries. `0` means unlimited.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict_least_recently_used();
+    }
+
+    /// Returns the value for `path` and marks it as used.
+    pub fn get(&self, path: &Path) -> Option<&V> {
+        let entry = self.entries.get(path)?;
+        entry.last_used.set(Instant::now());
+        Some(&entry.value)
+    }
+
+    /// Returns the value for `path` for updating it in place, without marking it as used.
+    pub fn get_mut(&mut self, path: &Path) -> Option<&mut V> {
+        self.entries.get_mut(path).map(|entry| &mut entry.value)
+    }
+
+    /// Inserts or replaces the value for `path`, evicting the least recently used entry if the
+    /// cache is full.
+    pub fn insert(&mut self, path: PathBuf, value: V) {
+        let now = Instant::now();
+        self.entries.insert(
+            path,
+            Entry {
+                value,
+                fetched_at: now,
+                last_used: Cell::new(now),
+            },
+        );
+        self.evict_least_recently_used();
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<V> {
+        self.entries.remove(path).map(|entry| entry.value)
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.keys().cloned().collect()
+    }
+
+    /// Splits the entries fetched more than `ttl(value)` before `now` into those that were read
+    /// since and should be refreshed, and those that weren't and can be dropped.
+    pub fn expired(
+        &self,
+        now: Instant,
+        ttl: impl Fn(&V) -> Duration,
+    ) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let mut used = Vec::new();
+        let mut unused = Vec::new();
+
+        for (path, entry) in &self.entries {
+            if now.saturating_duration_since(entry.fetched_at) < ttl(&entry.value) {
+                continue;
+            }
+
+            if entry.last_used.get() > entry.fetched_at {
+                used.push(path.clone());
+            } else {
+                unused.push(path.clone());
+            }
+        }
+
+        (used, unused)
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        while self.entries.len() > self.capacity {
+            let Some(path) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used.get())
+                .map(|(path, _)| path.clone())
+            else {
+                return;
+            };
+
+            self.entries.remove(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let mut cache = PathCache::default();
+        cache.set_capacity(2);
+
+        cache.insert(PathBuf::from("/a"), 1);
+        cache.insert(PathBuf::from("/b"), 2);
+        std::thread::sleep(Duration::from_millis(1));
+        cache.get(Path::new("/a"));
+        cache.insert(PathBuf::from("/c"), 3);
+
+        assert_eq!(
+            cache.paths(),
+            vec![PathBuf::from("/a"), PathBuf::from("/c")]
+        );
+    }
+
+    #[test]
+    fn splits_expired_entries_by_use() {
+        let mut cache = PathCache::default();
+
+        cache.insert(PathBuf::from("/used"), 1);
+        cache.insert(PathBuf::from("/unused"), 2);
+        std::thread::sleep(Duration::from_millis(1));
+        cache.get(Path::new("/used"));
+
+        let ttl = Duration::from_secs(10);
+
+        assert_eq!(cache.expired(Instant::now(), |_| ttl), (vec![], vec![]));
+        assert_eq!(
+            cache.expired(Instant::now() + ttl, |_| ttl),
+            (vec![PathBuf::from("/used")], vec![PathBuf::from("/unused")])
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use kdl::KdlNode;
+
+use crate::config_block::{parse_block, string_arguments};
+
+/// Replaces a leading part of a path with a shorter name.
+struct PathPrefix {
+    /// An absolute path, or one starting with `~` or an environment variable like `$GOPATH`
+    prefix: String,
+    replacement: String,
+}
+
+/// Shortened names for the beginnings of paths, from the `path_prefixes` block of the plugin
+/// configuration. The home directory is always shown as `~`.
+pub struct PathPrefixes {
+    prefixes: Vec<PathPrefix>,
+}
+
+impl Default for PathPrefixes {
+    fn default() -> Self {
+        PathPrefixes {
+            prefixes: vec![PathPrefix {
+                prefix: String::from("~"),
+                replacement: String::from("~"),
+            }],
+        }
+    }
+}
+
+impl PathPrefixes {
+    /// Parses prefixes like `"~/work" "W:"` or `"$GOPATH/src/github.com" "gh:"`.
+    pub fn from_configuration(configuration: &BTreeMap<String, String>) -> PathPrefixes {
+        let mut path_prefixes = PathPrefixes::default();
+
+        if let Some(prefixes) =
+            parse_block(configuration, "path_prefixes", "path prefix", parse_prefix)
+        {
+            path_prefixes.prefixes.extend(prefixes);
+        }
+
+        path_prefixes
+    }
+
+    /// The environment variables the prefixes refer to.
+    pub fn variables(&self) -> Vec<&str> {
+        self.prefixes
+            .iter()
+            .filter_map(|prefix| prefix.prefix.strip_prefix('$'))
+            .map(|variable| variable.split('/').next().unwrap_or_default())
+            .collect()
+    }
+
+    /// `path` with the longest matching prefix replaced. Prefixes only match whole path
+    /// components, and those referring to an unknown directory are skipped.
+    pub fn apply(
+        &self,
+        path: &Path,
+        home_dir: Option<&str>,
+        environment: &BTreeMap<String, String>,
+    ) -> Option<String> {
+        let (prefix, rest) = self
+            .prefixes
+            .iter()
+            .filter_map(|prefix| {
+                let resolved = prefix.resolve(home_dir, environment)?;
+                let rest = path.strip_prefix(&resolved).ok()?;
+                Some((prefix, resolved.components().count(), rest))
+            })
+            .max_by_key(|(_, length, _)| *length)
+            .map(|(prefix, _, rest)| (prefix, rest))?;
+
+        let rest = rest.to_str()?;
+
+        Some(
+            if rest.is_empty() || prefix.replacement.ends_with([':', '/']) {
+                format!("{}{rest}", prefix.replacement)
+            } else {
+                format!("{}/{rest}", prefix.replacement)
+            },
+        )
+    }
+}
+
+fn parse_prefix(node: &KdlNode) -> Result<PathPrefix, String> {
+    let prefix = node.name().value();
+
+    let arguments = string_arguments(node);
+    let [replacement] = arguments.as_slice() else {
+        return Err(String::from("expected a replacement"));
+    };
+
+    if !(prefix.starts_with(['/', '~', '$'])) {
+        return Err(String::from(
+            "expected an absolute path, ~ or an environment variable",
+        ));
+    }
+
+    Ok(PathPrefix {
+        prefix: prefix.trim_end_matches('/').to_string(),
+        replacement: (*replacement).to_string(),
+    })
+}
+
+impl PathPrefix {
+    /// The absolute path the prefix stands for, if the directories it refers to are known.
+    fn resolve(
+        &self,
+        home_dir: Option<&str>,
+        environment: &BTreeMap<String, String>,
+    ) -> Option<PathBuf> {
+        let (first, rest) = self
+            .prefix
+            .split_once('/')
+            .unwrap_or((self.prefix.as_str(), ""));
+
+        let root = if first == "~" {
+            home_dir?
+        } else if let Some(variable) = first.strip_prefix('$') {
+            environment.get(variable)?
+        } else {
+            return Some(PathBuf::from(&self.prefix));
+        };
+
+        if !root.starts_with('/') {
+            return None;
+        }
+
+        let mut resolved = PathBuf::from(root);
+        if !rest.is_empty() {
+            resolved.push(rest);
+        }
+
+        Some(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefixes(source: &str) -> PathPrefixes {
+        let mut configuration = BTreeMap::new();
+        configuration.insert(String::from("path_prefixes"), source.to_string());
+        PathPrefixes::from_configuration(&configuration)
+    }
+
+    #[test]
+    fn replaces_the_longest_matching_prefix() {
+        let prefixes = prefixes(
+            "\"~/work\" \"W:\"\n\
+             \"/mnt/nas/\" \"nas\"\n\
+             \"$GOPATH/src/github.com\" \"gh:\"\n\
+             \"relative\" \"r\"",
+        );
+        let mut environment = BTreeMap::new();
+        environment.insert(String::from("GOPATH"), String::from("/home/alice/go"));
+        let apply = |path: &str| prefixes.apply(Path::new(path), Some("/home/alice"), &environment);
+
+        assert_eq!(prefixes.variables(), vec!["GOPATH"]);
+        assert_eq!(apply("/home/alice/work/api"), Some("W:api".to_string()));
+        assert_eq!(
+            apply("/home/alice/workshop"),
+            Some("~/workshop".to_string())
+        );
+        assert_eq!(apply("/home/alice2/work"), None);
+        assert_eq!(apply("/mnt/nas/photos"), Some("nas/photos".to_string()));
+        assert_eq!(apply("/mnt/nas"), Some("nas".to_string()));
+        assert_eq!(
+            apply("/home/alice/go/src/github.com/x/y"),
+            Some("gh:x/y".to_string())
+        );
+        assert_eq!(apply("/home/alice/go/pkg"), Some("~/go/pkg".to_string()));
+        assert_eq!(prefixes.prefixes.len(), 4);
+    }
+
+    #[test]
+    fn skips_prefixes_of_unknown_directories() {
+        let prefixes = prefixes("\"$GOPATH/src\" \"go:\"");
+
+        assert_eq!(
+            prefixes.apply(Path::new("/home/alice/go/src"), None, &BTreeMap::new()),
+            None
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use kdl::KdlNode;
+use regex::Regex;
+
+use crate::config_block::{parse_block, string_arguments};
+
+enum PathPattern {
+    /// Path components, where `*` and `?` match within a component and `**` matches any number
+    /// of components. A leading `~` stands for the home directory.
+    Glob(Vec<String>),
+    /// Matched against the beginning of the absolute path.
+    Regex(Regex),
+}
+
+/// Replaces the part of a path matched by `pattern` with `name`, where `{N}` refers to the
+/// `N`th wildcard of a glob or capture group of a regex.
+struct PathRule {
+    pattern: PathPattern,
+    name: String,
+}
+
+/// Display names for directories, from the `path_rules` block of the plugin configuration.
+#[derive(Default)]
+pub struct PathRules {
+    rules: Vec<PathRule>,
+}
+
+impl PathRules {
+    /// Parses rules like `glob "~/work/monorepo/services/*" "svc:{1}"` or
+    /// `regex "^/srv/([^/]+)/current" "srv:{1}"`, in the order they are tried.
+    pub fn from_configuration(configuration: &BTreeMap<String, String>) -> PathRules {
+        PathRules {
+            rules: parse_block(configuration, "path_rules", "path rule", parse_rule)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The display name of `path` according to the first matching rule. Whatever follows the
+    /// matched part of the path is appended to the name.
+    pub fn apply(&self, path: &Path, home_dir: Option<&str>) -> Option<String> {
+        self.rules
+            .iter()
+            .find_map(|rule| rule.apply(path, home_dir))
+    }
+}
+
+fn parse_rule(node: &KdlNode) -> Result<PathRule, String> {
+    let arguments = string_arguments(node);
+    let [pattern, name] = arguments.as_slice() else {
+        return Err(String::from("expected a pattern and a name"));
+    };
+
+    let pattern = match node.name().value() {
+        "glob" => PathPattern::Glob(
+            pattern
+                .split('/')
+                .filter(|component| !component.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        "regex" => PathPattern::Regex(Regex::new(pattern).map_err(|err| err.to_string())?),
+        kind => return Err(format!("unknown kind {kind}, expected glob or regex")),
+    };
+
+    Ok(PathRule {
+        pattern,
+        name: (*name).to_string(),
+    })
+}
+
+impl PathRule {
+    fn apply(&self, path: &Path, home_dir: Option<&str>) -> Option<String> {
+        let path = path.to_str()?;
+
+        let (captures, rest) = match &self.pattern {
+            PathPattern::Glob(pattern) => {
+                let mut pattern: Vec<&str> = pattern.iter().map(String::as_str).collect();
+
+                if pattern.first() == Some(&"~") {
+                    let home_components = home_dir?.split('/').filter(|c| !c.is_empty());
+                    pattern.splice(..1, home_components);
+                }
+
+                let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+                let mut captures = Vec::new();
+                let matched = match_components(&pattern, &components, &mut captures)?;
+
+                (captures, components[matched..].join("/"))
+            }
+            PathPattern::Regex(regex) => {
+                let found = regex.captures(path)?;
+                let matched = found.get(0)?;
+
+                if matched.start() != 0 {
+                    return None;
+                }
+
+                let captures = found
+                    .iter()
+                    .skip(1)
+                    .map(|capture| capture.map_or_else(String::new, |c| c.as_str().to_string()))
+                    .collect();
+
+                (
+                    captures,
+                    path[matched.end()..].trim_matches('/').to_string(),
+                )
+            }
+        };
+
+        let name = expand_captures(&self.name, &captures);
+
+        if rest.is_empty() {
+            Some(name)
+        } else {
+            Some(format!("{name}/{rest}"))
+        }
+    }
+}
+
+/// Matches `pattern` against the beginning of `components`, collecting the values of the
+/// wildcards. Returns how many components were matched.
+fn match_components(
+    pattern: &[&str],
+    components: &[&str],
+    captures: &mut Vec<String>,
+) -> Option<usize> {
+    let Some((first, pattern_rest)) = pattern.split_first() else {
+        return Some(0);
+    };
+
+    if *first == "**" {
+        // Prefer matching as many components as possible
+        for taken in (0..=components.len()).rev() {
+            let mut inner_captures = vec![components[..taken].join("/")];
+            if let Some(matched) =
+                match_components(pattern_rest, &components[taken..], &mut inner_captures)
+            {
+                captures.append(&mut inner_captures);
+                return Some(taken + matched);
+            }
+        }
+        return None;
+    }
+
+    let (component, components_rest) = components.split_first()?;
+
+    if !match_wildcards(first, component) {
+        return None;
+    }
+
+    let mut inner_captures = Vec::new();
+    if first.contains(['*', '?']) {
+        inner_captures.push((*component).to_string());
+    }
+
+    let matched = match_components(pattern_rest, components_rest, &mut inner_captures)?;
+    captures.append(&mut inner_captures);
+    Some(matched + 1)
+}
+
+/// Matches a single path component against a pattern with `*` and `?` wildcards.
+fn match_wildcards(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    match_chars(&pattern, &value)
+}
+
+fn match_chars(pattern: &[char], value: &[char]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some(('*', rest)) => (0..=value.len()).any(|skip| match_chars(rest, &value[skip..])),
+        Some(('?', rest)) => !value.is_empty() && match_chars(rest, &value[1..]),
+        Some((c, rest)) => value.first() == Some(c) && match_chars(rest, &value[1..]),
+    }
+}
+
+/// Replaces `{N}` in `name` with the `N`th capture, counting from 1.
+fn expand_captures(name: &str, captures: &[String]) -> String {
+    let mut result = String::new();
+    let mut rest = name;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let index = rest[1..].find('}').and_then(|end| {
+            rest[1..=end]
+                .parse::<usize>()
+                .ok()
+                .map(|index| (index, end))
+        });
+
+        if let Some((index, end)) = index {
+            if let Some(capture) = index.checked_sub(1).and_then(|i| captures.get(i)) {
+                result.push_str(capture);
+            }
+            rest = &rest[end + 2..];
+        } else {
+            result.push('{');
+            rest = &rest[1..];
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(source: &str) -> PathRules {
+        let mut configuration = BTreeMap::new();
+        configuration.insert(String::from("path_rules"), source.to_string());
+        PathRules::from_configuration(&configuration)
+    }
+
+    #[test]
+    fn applies_glob_rules() {
+        let rules = rules(
+            "glob \"~/work/monorepo/services/*\" \"svc:{1}\"\n\
+             glob \"/var/log\" \"logs\"\n\
+             glob \"/srv/**/releases/v?\" \"{1} {2}\"",
+        );
+        let apply = |path: &str| rules.apply(Path::new(path), Some("/home/alice"));
+
+        assert_eq!(
+            apply("/home/alice/work/monorepo/services/auth"),
+            Some("svc:auth".to_string())
+        );
+        assert_eq!(
+            apply("/home/alice/work/monorepo/services/auth/src"),
+            Some("svc:auth/src".to_string())
+        );
+        assert_eq!(apply("/home/alice/work/monorepo/services"), None);
+        assert_eq!(apply("/var/log/nginx"), Some("logs/nginx".to_string()));
+        assert_eq!(apply("/var/logs"), None);
+        assert_eq!(
+            apply("/srv/a/b/releases/v2/bin"),
+            Some("a/b v2/bin".to_string())
+        );
+        assert_eq!(
+            rules.apply(Path::new("/home/alice/work/monorepo/services/auth"), None),
+            None
+        );
+    }
+
+    #[test]
+    fn applies_regex_rules_at_the_beginning_of_the_path() {
+        let rules = rules(
+            "regex \"/srv/([^/]+)/current\" \"srv:{1}{x}\"\n\
+             unknown \"/tmp\" \"tmp\"\n\
+             regex \"(\" \"broken\"",
+        );
+
+        assert_eq!(
+            rules.apply(Path::new("/srv/api/current/logs"), None),
+            Some("srv:api{x}/logs".to_string())
+        );
+        assert_eq!(
+            rules.apply(Path::new("/backup/srv/api/current"), None),
+            None
+        );
+        assert_eq!(rules.rules.len(), 1);
+    }
+}
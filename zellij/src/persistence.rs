@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::status::PaneStatus;
+
+/// Where the host directory returned by `state_dir` is mounted once the plugin switched to it.
+pub const STATE_DIR_MOUNT: &str = "/host";
+
+/// Prints those of the process ids given as arguments that don't belong to a running process.
+pub const DEAD_PROCESSES_SCRIPT: &str = r#"
+for pid; do
+    kill -0 "$pid" 2>/dev/null || echo "$pid"
+done
+"#;
+
+/// The directory on the host the state is kept in, `$XDG_STATE_HOME/zellij-tabula` or
+/// `~/.local/state/zellij-tabula`. Unlike the plugin's data directory, it outlives the plugin.
+pub fn state_dir(xdg_state_home: Option<&str>, home_dir: Option<&str>) -> Option<PathBuf> {
+    match xdg_state_home.filter(|dir| dir.starts_with('/')) {
+        Some(xdg_state_home) => Some(Path::new(xdg_state_home).join("zellij-tabula")),
+        None => home_dir
+            .filter(|dir| dir.starts_with('/'))
+            .map(|home_dir| Path::new(home_dir).join(".local/state/zellij-tabula")),
+    }
+}
+
+/// The part of the plugin state that is kept across plugin reloads and re-attaches.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PersistedState {
+    #[serde(default)]
+    pub pane_statuses: BTreeMap<u32, PersistedPaneStatus>,
+    #[serde(default)]
+    pub pane_working_dirs: BTreeMap<u32, PathBuf>,
+    /// The tabs tabula doesn't rename, by tab id
+    #[serde(default)]
+    pub locked_tabs: BTreeMap<usize, PersistedLock>,
+    /// Templates given as tab names, by tab id
+    #[serde(default)]
+    pub tab_templates: BTreeMap<usize, PersistedTabTemplate>,
+}
+
+/// A locked tab, with the name the user gave it and the name it has with its status glyph.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct PersistedLock {
+    pub label: String,
+    pub name: String,
+}
+
+/// A template given as a tab name, with the name tabula rendered from it.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct PersistedTabTemplate {
+    pub template: String,
+    pub name: String,
+}
+
+/// A `PaneStatus` with its expiry as wall clock time, since an `Instant` means nothing after a
+/// reload.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct PersistedPaneStatus {
+    pub name: String,
+    #[serde(default)]
+    pub set_on_focus: bool,
+    /// Seconds since the unix epoch
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub on_expire: Option<String>,
+}
+
+impl PersistedPaneStatus {
+    pub fn capture(pane_status: &PaneStatus, now: Instant, system_now: SystemTime) -> Self {
+        PersistedPaneStatus {
+            name: pane_status.name.clone(),
+            set_on_focus: pane_status.set_on_focus,
+            expires_at: pane_status.expires_at.map(|expires_at| {
+                unix_seconds(system_now + expires_at.saturating_duration_since(now))
+            }),
+            on_expire: pane_status.on_expire.clone(),
+        }
+    }
+
+    /// Turns the persisted status back into a `PaneStatus` set at `now`. A status that expired
+    /// in the meantime expires at `now`.
+    pub fn restore(&self, now: Instant, system_now: SystemTime) -> PaneStatus {
+        PaneStatus {
+            set_on_focus: self.set_on_focus,
+            expires_at: self.expires_at.map(|expires_at| {
+                now + Duration::from_secs(expires_at.saturating_sub(unix_seconds(system_now)))
+            }),
+            on_expire: self.on_expire.clone(),
+            ..PaneStatus::new(&self.name, now)
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// The file the state of the session is kept in. Pane and tab ids start over in every session,
+/// so a session resurrected under the same name gets a file of its own, told apart by the
+/// process id of the zellij server running it.
+fn state_file(dir: &Path, session_name: &str, zellij_pid: u32) -> PathBuf {
+    let file_name: String = session_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    dir.join(format!("session-{file_name}-{zellij_pid}.json"))
+}
+
+/// The zellij server process id a state file belongs to.
+fn state_file_pid(path: &Path) -> Option<u32> {
+    let file_name = path.file_name()?.to_str()?;
+    let stem = file_name.strip_prefix("session-")?.strip_suffix(".json")?;
+    stem.rsplit_once('-')?.1.parse().ok()
+}
+
+pub fn load(dir: &Path, session_name: &str, zellij_pid: u32) -> Option<PersistedState> {
+    let path = state_file(dir, session_name, zellij_pid);
+    let contents = std::fs::read_to_string(&path).ok()?;
+
+    match serde_json::from_str(&contents) {
+        Ok(state) => Some(state),
+        Err(err) => {
+            eprintln!("Failed to parse {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Writes the state of the session unless it serializes to `previous`. Returns the serialized
+/// state if it was written.
+pub fn save(
+    dir: &Path,
+    session_name: &str,
+    zellij_pid: u32,
+    state: &PersistedState,
+    previous: Option<&str>,
+) -> Option<String> {
+    let path = state_file(dir, session_name, zellij_pid);
+
+    let contents = match serde_json::to_string(state) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("Failed to serialize the state: {err}");
+            return None;
+        }
+    };
+
+    if previous == Some(contents.as_str()) {
+        return None;
+    }
+
+    if let Err(err) = std::fs::write(&path, &contents) {
+        eprintln!("Failed to write {}: {err}", path.display());
+        return None;
+    }
+
+    Some(contents)
+}
+
+/// Deletes the state file of a session, e.g. after it was renamed.
+pub fn remove(dir: &Path, session_name: &str, zellij_pid: u32) {
+    let path = state_file(dir, session_name, zellij_pid);
+
+    if let Err(err) = std::fs::remove_file(&path) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            eprintln!("Failed to delete {}: {err}", path.display());
+        }
+    }
+}
+
+/// The process ids of the zellij servers other than `zellij_pid` that state files were written
+/// by.
+pub fn other_servers(dir: &Path, zellij_pid: u32) -> BTreeSet<u32> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return BTreeSet::new();
+    };
+
+    entries
+        .filter_map(|entry| state_file_pid(&entry.ok()?.path()))
+        .filter(|pid| *pid != zellij_pid)
+        .collect()
+}
+
+/// Deletes the state files written by the zellij servers `pids`, which are no longer running.
+pub fn remove_servers(dir: &Path, pids: &BTreeSet<u32>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if state_file_pid(&path).is_some_and(|pid| pids.contains(&pid)) {
+            if let Err(err) = std::fs::remove_file(&path) {
+                eprintln!("Failed to delete {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_the_remaining_ttl_of_pane_statuses() {
+        let now = Instant::now();
+        let system_now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let pane_status = PaneStatus {
+            expires_at: Some(now + Duration::from_mins(10)),
+            on_expire: Some("error".to_string()),
+            ..PaneStatus::new("running", now)
+        };
+
+        let persisted = PersistedPaneStatus::capture(&pane_status, now, system_now);
+        assert_eq!(persisted.expires_at, Some(1_600));
+
+        let later = now + Duration::from_secs(5);
+        let restored = persisted.restore(later, system_now + Duration::from_secs(100));
+        assert_eq!(restored.name, "running");
+        assert_eq!(restored.on_expire, Some("error".to_string()));
+        assert_eq!(restored.expires_at, Some(later + Duration::from_secs(500)));
+
+        let restored = persisted.restore(later, system_now + Duration::from_mins(15));
+        assert_eq!(restored.expires_at, Some(later));
+    }
+
+    #[test]
+    fn keeps_session_names_inside_the_state_directory() {
+        let path = state_file(Path::new("/host"), "../my-session", 42);
+        assert_eq!(path, PathBuf::from("/host/session-___my-session-42.json"));
+        assert_eq!(state_file_pid(&path), Some(42));
+    }
+
+    #[test]
+    fn saves_and_loads_the_state_of_sessions() {
+        let dir = std::env::temp_dir().join(format!("zellij-tabula-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut state = PersistedState::default();
+        state
+            .pane_working_dirs
+            .insert(1, PathBuf::from("/home/alice/project"));
+        state.locked_tabs.insert(
+            7,
+            PersistedLock {
+                label: "notes".to_string(),
+                name: "⏳notes".to_string(),
+            },
+        );
+
+        let contents = save(&dir, "main", 100, &state, None);
+        assert!(contents.is_some());
+        assert_eq!(save(&dir, "main", 100, &state, contents.as_deref()), None);
+        assert_eq!(load(&dir, "main", 100), Some(state));
+        assert_eq!(load(&dir, "main", 200), None);
+
+        save(&dir, "main", 200, &PersistedState::default(), None);
+        save(&dir, "other", 300, &PersistedState::default(), None);
+        assert_eq!(other_servers(&dir, 100), BTreeSet::from([200, 300]));
+
+        remove_servers(&dir, &BTreeSet::from([200]));
+        remove(&dir, "other", 300);
+        assert_eq!(other_servers(&dir, 100), BTreeSet::new());
+        assert!(load(&dir, "main", 100).is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
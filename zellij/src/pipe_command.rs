@@ -0,0 +1,304 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// A command sent to the `tabula` pipe.
+#[derive(Debug, Eq, PartialEq)]
+pub enum PipeCommand {
+    /// Sets the status of a pane, the status names are validated by the caller.
+    SetStatus {
+        pane_id: u32,
+        status: String,
+        /// How long until the status expires
+        ttl: Option<Duration>,
+        /// The status to switch to when it expires, `None` clears it
+        on_expire: Option<String>,
+    },
+    /// Re-fetches the metadata of all cached paths.
+    Refresh,
+    /// Stops renaming a tab, the active one if no tab id is given.
+    Lock { tab_id: Option<usize> },
+    /// Resumes renaming a tab, the active one if no tab id is given.
+    Unlock { tab_id: Option<usize> },
+}
+
+/// The syntax a pipe message was written in, replies use the same one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PipeFormat {
+    /// `status '<pane_id>' '<status>' ttl=<seconds>`, `lock <tab_id>`
+    Legacy,
+    /// `{"command": "status", "pane_id": 1, "status": "waiting"}`
+    Json,
+}
+
+impl PipeFormat {
+    /// The reply to send back to the CLI. Legacy messages only get a reply if they failed.
+    pub fn reply(self, result: &Result<(), String>) -> Option<String> {
+        match (self, result) {
+            (PipeFormat::Json, Ok(())) => Some(serde_json::json!({ "ok": true }).to_string()),
+            (PipeFormat::Json, Err(err)) => {
+                Some(serde_json::json!({ "ok": false, "error": err }).to_string())
+            }
+            (PipeFormat::Legacy, Ok(())) => None,
+            (PipeFormat::Legacy, Err(err)) => Some(format!("error: {err}")),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+enum JsonCommand {
+    Status {
+        pane_id: JsonId,
+        status: String,
+        ttl: Option<u64>,
+        on_expire: Option<String>,
+    },
+    Refresh,
+    Lock {
+        tab_id: Option<JsonId>,
+    },
+    Unlock {
+        tab_id: Option<JsonId>,
+    },
+}
+
+/// Pane and tab ids are accepted as numbers and as strings, e.g. from `$ZELLIJ_PANE_ID`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonId {
+    Number(u64),
+    String(String),
+}
+
+impl JsonId {
+    fn parse<T: TryFrom<u64> + FromStr>(self, kind: &str) -> Result<T, String> {
+        match self {
+            JsonId::Number(id) => T::try_from(id).map_err(|_| format!("Invalid {kind} id: {id}")),
+            JsonId::String(id) => id
+                .trim()
+                .parse()
+                .map_err(|_| format!("Failed to parse {kind} id: {id}")),
+        }
+    }
+}
+
+/// Parses a pipe payload, either as JSON if it starts with `{` or in the legacy syntax.
+pub fn parse_pipe_command(payload: &str) -> (PipeFormat, Result<PipeCommand, String>) {
+    let payload = payload.trim();
+
+    if payload.starts_with('{') {
+        (PipeFormat::Json, parse_json_command(payload))
+    } else {
+        (PipeFormat::Legacy, parse_legacy_command(payload))
+    }
+}
+
+fn parse_json_command(payload: &str) -> Result<PipeCommand, String> {
+    let command: JsonCommand =
+        serde_json::from_str(payload).map_err(|err| format!("Invalid command: {err}"))?;
+
+    Ok(match command {
+        JsonCommand::Status {
+            pane_id,
+            status,
+            ttl,
+            on_expire,
+        } => PipeCommand::SetStatus {
+            pane_id: pane_id.parse("pane")?,
+            status,
+            ttl: ttl.map(Duration::from_secs),
+            on_expire,
+        },
+        JsonCommand::Refresh => PipeCommand::Refresh,
+        JsonCommand::Lock { tab_id } => PipeCommand::Lock {
+            tab_id: tab_id.map(|tab_id| tab_id.parse("tab")).transpose()?,
+        },
+        JsonCommand::Unlock { tab_id } => PipeCommand::Unlock {
+            tab_id: tab_id.map(|tab_id| tab_id.parse("tab")).transpose()?,
+        },
+    })
+}
+
+fn parse_legacy_command(payload: &str) -> Result<PipeCommand, String> {
+    if payload == "refresh" {
+        return Ok(PipeCommand::Refresh);
+    }
+
+    let parts: Vec<&str> = payload.split(' ').collect();
+
+    if let ["lock" | "unlock", rest @ ..] = parts.as_slice() {
+        let tab_id = match rest {
+            [] => None,
+            [tab_id] => match unquote(tab_id).parse::<usize>() {
+                Ok(tab_id) => Some(tab_id),
+                Err(_) => return Err(format!("Failed to parse tab id: {tab_id}")),
+            },
+            _ => return Err(format!("Expected at most a tab id, got {}", rest.join(" "))),
+        };
+
+        return Ok(if parts[0] == "lock" {
+            PipeCommand::Lock { tab_id }
+        } else {
+            PipeCommand::Unlock { tab_id }
+        });
+    }
+
+    if payload.starts_with("status ") {
+        if parts.len() < 3 {
+            return Err(format!(
+                "Expected at least 3 parts for status update, got {}",
+                parts.len()
+            ));
+        }
+
+        let Ok(pane_id) = rem_first_and_last(parts[1]).parse::<u32>() else {
+            return Err(format!("Failed to parse pane id: {}", parts[1]));
+        };
+
+        let mut ttl = None;
+        let mut on_expire = None;
+
+        for option in &parts[3..] {
+            match option.split_once('=') {
+                Some(("ttl", value)) => {
+                    let Ok(seconds) = unquote(value).parse::<u64>() else {
+                        return Err(format!("Failed to parse ttl: {value}"));
+                    };
+                    ttl = Some(Duration::from_secs(seconds));
+                }
+                Some(("on_expire", value)) => on_expire = Some(unquote(value).to_string()),
+                _ => return Err(format!("Unknown status option: {option}")),
+            }
+        }
+
+        return Ok(PipeCommand::SetStatus {
+            pane_id,
+            status: rem_first_and_last(parts[2]).to_string(),
+            ttl,
+            on_expire,
+        });
+    }
+
+    Err(format!("Unknown command: {payload}"))
+}
+
+fn rem_first_and_last(value: &str) -> &str {
+    let mut chars = value.chars();
+    chars.next();
+    chars.next_back();
+    chars.as_str()
+}
+
+/// Removes the quotes around an option value, if there are any.
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        rem_first_and_last(value)
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_legacy_status_commands() {
+        assert_eq!(
+            parse_pipe_command("status '12' 'waiting'"),
+            (
+                PipeFormat::Legacy,
+                Ok(PipeCommand::SetStatus {
+                    pane_id: 12,
+                    status: "waiting".to_string(),
+                    ttl: None,
+                    on_expire: None,
+                })
+            )
+        );
+        assert_eq!(
+            parse_pipe_command("status '12' 'running' ttl=600 on_expire='error'").1,
+            Ok(PipeCommand::SetStatus {
+                pane_id: 12,
+                status: "running".to_string(),
+                ttl: Some(Duration::from_mins(10)),
+                on_expire: Some("error".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_pipe_command("status '12' waiting now").1,
+            Err("Unknown status option: now".to_string())
+        );
+        assert_eq!(
+            parse_pipe_command("status '12'").1,
+            Err("Expected at least 3 parts for status update, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_json_commands() {
+        assert_eq!(
+            parse_pipe_command(
+                r#"{"command": "status", "pane_id": "12", "status": "waiting", "ttl": 60}"#
+            ),
+            (
+                PipeFormat::Json,
+                Ok(PipeCommand::SetStatus {
+                    pane_id: 12,
+                    status: "waiting".to_string(),
+                    ttl: Some(Duration::from_mins(1)),
+                    on_expire: None,
+                })
+            )
+        );
+        assert_eq!(
+            parse_pipe_command(r#"{"command": "refresh"}"#).1,
+            Ok(PipeCommand::Refresh)
+        );
+        assert!(
+            parse_pipe_command(r#"{"command": "status", "pane_id": 12}"#)
+                .1
+                .is_err()
+        );
+        assert_eq!(
+            parse_pipe_command(r#"{"command": "status", "pane_id": "x", "status": "none"}"#).1,
+            Err("Failed to parse pane id: x".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_lock_commands() {
+        assert_eq!(
+            parse_pipe_command("lock 3").1,
+            Ok(PipeCommand::Lock { tab_id: Some(3) })
+        );
+        assert_eq!(
+            parse_pipe_command("unlock").1,
+            Ok(PipeCommand::Unlock { tab_id: None })
+        );
+        assert_eq!(
+            parse_pipe_command(r#"{"command": "lock", "tab_id": "3"}"#).1,
+            Ok(PipeCommand::Lock { tab_id: Some(3) })
+        );
+        assert_eq!(
+            parse_pipe_command("lock x").1,
+            Err("Failed to parse tab id: x".to_string())
+        );
+    }
+
+    #[test]
+    fn replies_in_the_format_of_the_command() {
+        assert_eq!(
+            PipeFormat::Json.reply(&Ok(())),
+            Some(r#"{"ok":true}"#.to_string())
+        );
+        assert_eq!(
+            PipeFormat::Json.reply(&Err("Unknown pane status: busy".to_string())),
+            Some(r#"{"error":"Unknown pane status: busy","ok":false}"#.to_string())
+        );
+        assert_eq!(PipeFormat::Legacy.reply(&Ok(())), None);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use kdl::KdlNode;
+
+use crate::config_block::{parse_block, string_arguments};
+
+/// Prints the root of the project the current directory is in, found by the first detector
+/// given as an argument that finds one. Arguments starting with `!` are commands printing the
+/// root, all others are files marking it. A `Cargo.toml` marker is followed up to the root of
+/// the closest workspace, like cargo does.
+pub const PROJECT_ROOT_SCRIPT: &str = r#"
+for detector; do
+    case $detector in
+    !*)
+        if root=$(${detector#!} 2>/dev/null) && [ -n "$root" ]; then
+            echo "$root"
+            exit 0
+        fi
+        ;;
+    *)
+        dir=$PWD
+        while :; do
+            if [ -e "$dir/$detector" ]; then
+                if [ "$detector" = Cargo.toml ] && ! grep -qs '^\[workspace\]' "$dir/Cargo.toml"; then
+                    parent=$dir
+                    while [ "$parent" != / ]; do
+                        parent=$(dirname "$parent")
+                        if grep -qs '^\[workspace\]' "$parent/Cargo.toml"; then
+                            dir=$parent
+                            break
+                        fi
+                    done
+                fi
+                echo "$dir"
+                exit 0
+            fi
+            if [ "$dir" = / ]; then break; fi
+            dir=$(dirname "$dir")
+        done
+        ;;
+    esac
+done
+exit 1
+"#;
+
+/// Prints the directory and name of the closest workspace member containing the current
+/// directory below the project root given as the first argument: a cargo crate, an npm or pnpm
+/// package, an Nx project or a Go module. Prints nothing outside of workspace members.
+///
+/// This is a heuristic, the workspace globs aren't checked: any named manifest counts, except
+/// for those in `node_modules`. Only the top-level `name` of a JSON manifest is used, which
+/// assumes it isn't minified.
+pub const PACKAGE_SCRIPT: &str = r#"
+json_name() {
+    awk '
+        depth == 1 && match($0, /^[ \t]*"name"[ \t]*:[ \t]*"[^"]*"/) {
+            name = substr($0, RSTART, RLENGTH)
+            sub(/^[^:]*:[ \t]*"/, "", name)
+            sub(/"$/, "", name)
+            print name
+            exit
+        }
+        {
+            line = $0
+            gsub(/"([^"\\]|\\.)*"/, "", line)
+            depth += gsub(/[{[]/, "", line) - gsub(/[]}]/, "", line)
+        }
+    ' "$1"
+}
+
+root=$1
+dir=$PWD
+while [ "$dir" != "$root" ] && [ "$dir" != / ]; do
+    name=
+    case $dir in
+    */node_modules | */node_modules/*)
+        dir=$(dirname "$dir")
+        continue
+        ;;
+    esac
+    if [ -f "$dir/Cargo.toml" ]; then
+        name=$(sed -n '/^\[package\]/,/^\[/s/^name *= *"\([^"]*\)".*/\1/p' "$dir/Cargo.toml")
+    fi
+    for manifest in package.json project.json; do
+        if [ -z "$name" ] && [ -f "$dir/$manifest" ]; then
+            name=$(json_name "$dir/$manifest")
+        fi
+    done
+    if [ -z "$name" ] && [ -f "$dir/go.mod" ]; then
+        name=$(sed -n 's/^module *//p' "$dir/go.mod")
+        name=${name##*/}
+    fi
+    name=$(printf '%s\n' "$name" | head -n 1)
+    if [ -n "$name" ]; then
+        printf '%s\n%s\n' "$dir" "$name"
+        exit 0
+    fi
+    dir=$(dirname "$dir")
+done
+"#;
+
+/// A workspace member, e.g. a crate of a cargo workspace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub root: PathBuf,
+}
+
+/// The package printed by `PACKAGE_SCRIPT`, if any.
+pub fn parse_package_output(stdout: &str) -> Option<Package> {
+    let mut lines = stdout.lines();
+    let root = PathBuf::from(lines.next()?);
+    let name = lines.next()?.trim();
+
+    if !root.is_absolute() || name.is_empty() {
+        return None;
+    }
+
+    Some(Package {
+        name: name.to_string(),
+        root,
+    })
+}
+
+/// A way of finding the root of a project outside of git.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ProjectDetector {
+    /// A command printing the root, like `jj root`. It is split at spaces.
+    Command(String),
+    /// A file in the root, like `go.mod`. The closest directory containing it is the root.
+    Marker(String),
+}
+
+/// How projects outside of git are recognized, from the `project_detectors` block of the
+/// plugin configuration. The detectors are tried in order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProjectDetectors {
+    detectors: Vec<ProjectDetector>,
+}
+
+impl Default for ProjectDetectors {
+    /// The version control systems other than git. Marker files have to be configured, since
+    /// e.g. a stray `~/package.json` would otherwise turn the whole home directory into a
+    /// project.
+    fn default() -> Self {
+        ProjectDetectors {
+            detectors: ["jj root", "hg root", "sl root"]
+                .iter()
+                .map(|command| ProjectDetector::Command((*command).to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl ProjectDetectors {
+    /// Parses detectors like `command "jj root"` or `marker "go.mod"`. An empty block turns
+    /// the detection off.
+    pub fn from_configuration(configuration: &BTreeMap<String, String>) -> ProjectDetectors {
+        parse_block(
+            configuration,
+            "project_detectors",
+            "project detector",
+            parse_detector,
+        )
+        .map_or_else(ProjectDetectors::default, |detectors| ProjectDetectors {
+            detectors,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.detectors.is_empty()
+    }
+
+    /// The detectors as arguments to `PROJECT_ROOT_SCRIPT`.
+    pub fn arguments(&self) -> Vec<String> {
+        self.detectors
+            .iter()
+            .map(|detector| match detector {
+                ProjectDetector::Command(command) => format!("!{command}"),
+                ProjectDetector::Marker(marker) => marker.clone(),
+            })
+            .collect()
+    }
+}
+
+fn parse_detector(node: &KdlNode) -> Result<ProjectDetector, String> {
+    let arguments = string_arguments(node);
+    let [argument] = arguments.as_slice() else {
+        return Err(String::from("expected a single argument"));
+    };
+
+    match node.name().value() {
+        "command" if !argument.trim().is_empty() => {
+            Ok(ProjectDetector::Command((*argument).to_string()))
+        }
+        "marker"
+            if !argument.is_empty() && !argument.contains('/') && !argument.starts_with('!') =>
+        {
+            Ok(ProjectDetector::Marker((*argument).to_string()))
+        }
+        "command" | "marker" => Err(format!("invalid argument {argument:?}")),
+        kind => Err(format!("unknown kind {kind}, expected command or marker")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_packages() {
+        assert_eq!(
+            parse_package_output("/home/alice/monorepo/crates/foo\nfoo\n"),
+            Some(Package {
+                name: "foo".to_string(),
+                root: PathBuf::from("/home/alice/monorepo/crates/foo"),
+            })
+        );
+        assert_eq!(parse_package_output(""), None);
+    }
+
+    #[test]
+    fn parses_detectors_from_configuration() {
+        let mut configuration = BTreeMap::new();
+        configuration.insert(
+            String::from("project_detectors"),
+            String::from(
+                "command \"jj root\"\n\
+                 marker \"build.zig\"\n\
+                 marker \"a/b\"\n\
+                 glob \"*.sln\"",
+            ),
+        );
+
+        let detectors = ProjectDetectors::from_configuration(&configuration);
+        assert_eq!(detectors.arguments(), vec!["!jj root", "build.zig"]);
+
+        configuration.insert(String::from("project_detectors"), String::new());
+        assert!(ProjectDetectors::from_configuration(&configuration).is_empty());
+
+        assert_eq!(
+            ProjectDetectors::default().arguments(),
+            vec!["!jj root", "!hg root", "!sl root"]
+        );
+    }
+}
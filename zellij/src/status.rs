@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use kdl::{KdlDocument, KdlNode};
+
+/// The name of the status that clears a pane's status.
+pub const NONE: &str = "none";
+
+/// A status a pane can be set to through the `tabula` pipe.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StatusDefinition {
+    pub name: String,
+    /// Shown in front of the tab name
+    pub glyph: String,
+    /// If the panes of a tab have different statuses, the one with the highest priority is shown
+    pub priority: i64,
+    /// The status to switch to once the pane has been focused for `focus_delay`, `none` clears it
+    pub on_focus: Option<String>,
+    pub focus_delay: Duration,
+}
+
+impl StatusDefinition {
+    fn new(name: &str, glyph: &str, priority: i64) -> StatusDefinition {
+        StatusDefinition {
+            name: name.to_string(),
+            glyph: glyph.to_string(),
+            priority,
+            on_focus: None,
+            focus_delay: Duration::ZERO,
+        }
+    }
+
+    /// Makes the status behave like an unread notification.
+    fn cleared_on_focus(mut self) -> StatusDefinition {
+        self.on_focus = Some(NONE.to_string());
+        self
+    }
+}
+
+/// The status a pane is currently set to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaneStatus {
+    pub name: String,
+    pub set_at: Instant,
+    /// Whether the status was reached through `on_focus`, which then doesn't apply again
+    pub set_on_focus: bool,
+    /// When the status expires, `None` if it doesn't
+    pub expires_at: Option<Instant>,
+    /// The status to switch to when it expires, `None` clears it
+    pub on_expire: Option<String>,
+}
+
+impl PaneStatus {
+    pub fn new(name: &str, set_at: Instant) -> PaneStatus {
+        PaneStatus {
+            name: name.to_string(),
+            set_at,
+            set_on_focus: false,
+            expires_at: None,
+            on_expire: None,
+        }
+    }
+}
+
+/// The statuses known to the plugin: the built-in ones, adjusted and extended by the
+/// configuration.
+#[derive(Clone, Debug)]
+pub struct StatusRegistry {
+    statuses: Vec<StatusDefinition>,
+}
+
+impl Default for StatusRegistry {
+    fn default() -> Self {
+        StatusRegistry {
+            statuses: vec![
+                StatusDefinition::new(NONE, "", 0),
+                StatusDefinition::new("success", "✅", 10),
+                StatusDefinition::new("running", "🔄", 20),
+                StatusDefinition::new("done-unseen", "📬", 30).cleared_on_focus(),
+                StatusDefinition::new("waiting", "⏳", 40),
+                StatusDefinition::new("attention", "🔔", 50).cleared_on_focus(),
+                StatusDefinition::new("error", "❌", 60),
+            ],
+        }
+    }
+}
+
+impl StatusRegistry {
+    /// Builds the registry from the `status_glyph_<name>` and `status_priority_<name>` keys and
+    /// the `statuses` block of the plugin configuration.
+    pub fn from_configuration(configuration: &BTreeMap<String, String>) -> StatusRegistry {
+        let mut registry = StatusRegistry::default();
+
+        for status in &mut registry.statuses {
+            let config_key = status.name.replace('-', "_");
+
+            if let Some(glyph) = configuration.get(&format!("status_glyph_{config_key}")) {
+                status.glyph.clone_from(glyph);
+            }
+
+            if let Some(priority) = configuration
+                .get(&format!("status_priority_{config_key}"))
+                .and_then(|priority| priority.parse::<i64>().ok())
+            {
+                status.priority = priority;
+            }
+        }
+
+        if let Some(statuses) = configuration.get("statuses") {
+            match statuses.parse::<KdlDocument>() {
+                Ok(document) => {
+                    for node in document.nodes() {
+                        registry.define(node);
+                    }
+                }
+                Err(err) => eprintln!("Failed to parse statuses: {err}"),
+            }
+        }
+
+        registry
+    }
+
+    /// Adds or updates a status from a node like
+    /// `deploying glyph="🚀" priority=45 on_focus="success" focus_delay=2`.
+    fn define(&mut self, node: &KdlNode) {
+        let name = node.name().value();
+
+        if name == NONE {
+            eprintln!("The {NONE} status can't be redefined");
+            return;
+        }
+
+        if self.get(name).is_none() {
+            self.statuses.push(StatusDefinition::new(name, "", 0));
+        }
+
+        let Some(status) = self.statuses.iter_mut().find(|status| status.name == name) else {
+            return;
+        };
+
+        for entry in node.entries() {
+            let Some(key) = entry.name().map(kdl::KdlIdentifier::value) else {
+                eprintln!("Ignoring unnamed value {} of status {name}", entry.value());
+                continue;
+            };
+
+            let value = entry.value();
+            let valid = match key {
+                "glyph" => value
+                    .as_string()
+                    .map(|glyph| status.glyph = glyph.to_string()),
+                "priority" => value.as_i64().map(|priority| status.priority = priority),
+                "clear_on_focus" => value.as_bool().map(|clear_on_focus| {
+                    status.on_focus = clear_on_focus.then(|| NONE.to_string());
+                }),
+                "on_focus" => value
+                    .as_string()
+                    .map(|on_focus| status.on_focus = Some(on_focus.to_string())),
+                "focus_delay" => value
+                    .as_i64()
+                    .and_then(|seconds| u64::try_from(seconds).ok())
+                    .map(|seconds| status.focus_delay = Duration::from_secs(seconds)),
+                _ => None,
+            };
+
+            if valid.is_none() {
+                eprintln!("Ignoring invalid property {key}={value} of status {name}");
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StatusDefinition> {
+        self.statuses.iter().find(|status| status.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_overrides_and_custom_statuses_from_configuration() {
+        let mut configuration = BTreeMap::new();
+        configuration.insert(String::from("status_glyph_done_unseen"), String::from("*"));
+        configuration.insert(
+            String::from("statuses"),
+            String::from(
+                "deploying glyph=\"🚀\" priority=45 clear_on_focus=true focus_delay=3\n\
+                 waiting priority=70 colour=\"red\"\n\
+                 attention on_focus=\"waiting\"",
+            ),
+        );
+
+        let registry = StatusRegistry::from_configuration(&configuration);
+
+        assert_eq!(registry.get("done-unseen").unwrap().glyph, "*");
+        assert_eq!(
+            registry.get("deploying"),
+            Some(&StatusDefinition {
+                name: "deploying".to_string(),
+                glyph: "🚀".to_string(),
+                priority: 45,
+                on_focus: Some("none".to_string()),
+                focus_delay: Duration::from_secs(3),
+            })
+        );
+        assert_eq!(
+            registry.get("waiting"),
+            Some(&StatusDefinition::new("waiting", "⏳", 70))
+        );
+        assert_eq!(
+            registry.get("attention").unwrap().on_focus.as_deref(),
+            Some("waiting")
+        );
+        assert_eq!(registry.get("busy"), None);
+    }
+}
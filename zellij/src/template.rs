@@ -0,0 +1,344 @@
+use std::fmt;
+
+/// A value that can be referenced from a tab name template as `{name}`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Placeholder {
+    /// The status glyph of the tab, e.g. `⏳`, or nothing.
+    Status,
+    /// The name tabula would generate without a template.
+    Name,
+    /// The shortened path (repo-relative inside git, `~`-relative otherwise).
+    Path,
+    /// The absolute path.
+    AbsPath,
+    /// The last component of the path.
+    Dir,
+    /// The name of the git repository.
+    Repo,
+    /// The path relative to the root of the git worktree.
+    RelPath,
+    /// The name of the linked git worktree, truncated to `worktree_name_preview_length`.
+    Worktree,
+    /// The absolute path of the git worktree root.
+    WorktreeRoot,
+    /// The checked out git branch.
+    Branch,
+    /// The abbreviated commit of a detached `HEAD`.
+    Commit,
+    /// The branch, or the abbreviated commit if `HEAD` is detached.
+    Head,
+    /// The git operation in progress, e.g. `rebase`.
+    Operation,
+    /// Compact glyphs for uncommitted changes, commits ahead/behind upstream and stashes.
+    GitStatus,
+    /// The name of the superproject, if the git worktree is a submodule.
+    Superproject,
+    /// The name of the workspace member, e.g. a crate of a cargo workspace.
+    Package,
+    /// The path relative to the root of the workspace member.
+    PackagePath,
+    /// `/` if several panes share the directory, `/*` if they don't, nothing otherwise.
+    MultiSuffix,
+    /// The number of terminal panes in the tab.
+    PaneCount,
+    /// The command running in the focused or only pane, e.g. `cargo test`.
+    Command,
+    /// The name rendered from `tab_name_template`, for templates given as tab names.
+    Auto,
+}
+
+impl Placeholder {
+    const ALL: [Placeholder; 21] = [
+        Placeholder::Status,
+        Placeholder::Name,
+        Placeholder::Path,
+        Placeholder::AbsPath,
+        Placeholder::Dir,
+        Placeholder::Repo,
+        Placeholder::RelPath,
+        Placeholder::Worktree,
+        Placeholder::WorktreeRoot,
+        Placeholder::Branch,
+        Placeholder::Commit,
+        Placeholder::Head,
+        Placeholder::Operation,
+        Placeholder::GitStatus,
+        Placeholder::Superproject,
+        Placeholder::Package,
+        Placeholder::PackagePath,
+        Placeholder::MultiSuffix,
+        Placeholder::PaneCount,
+        Placeholder::Command,
+        Placeholder::Auto,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Placeholder::Status => "status",
+            Placeholder::Name => "name",
+            Placeholder::Path => "path",
+            Placeholder::AbsPath => "abs_path",
+            Placeholder::Dir => "dir",
+            Placeholder::Repo => "repo",
+            Placeholder::RelPath => "rel_path",
+            Placeholder::Worktree => "worktree",
+            Placeholder::WorktreeRoot => "worktree_root",
+            Placeholder::Branch => "branch",
+            Placeholder::Commit => "commit",
+            Placeholder::Head => "head",
+            Placeholder::Operation => "operation",
+            Placeholder::GitStatus => "git_status",
+            Placeholder::Superproject => "superproject",
+            Placeholder::Package => "package",
+            Placeholder::PackagePath => "package_path",
+            Placeholder::MultiSuffix => "multi_suffix",
+            Placeholder::PaneCount => "pane_count",
+            Placeholder::Command => "command",
+            Placeholder::Auto => "auto",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Placeholder> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|placeholder| placeholder.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+    /// Only rendered if at least one placeholder inside renders to a non-empty value.
+    Optional(Vec<Segment>),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    UnclosedBrace,
+    UnexpectedClosingBrace,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => write!(f, "unknown placeholder {{{name}}}"),
+            TemplateError::UnclosedBrace => write!(f, "unclosed '{{'"),
+            TemplateError::UnexpectedClosingBrace => {
+                write!(f, "unexpected '}}', use '}}}}' for a literal brace")
+            }
+        }
+    }
+}
+
+/// A parsed tab name template.
+///
+/// `{placeholder}` inserts a value, `{?...}` renders its contents only if one of the
+/// placeholders inside is non-empty, and `{{` / `}}` produce literal braces.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Default for Template {
+    /// `{status}{name}`, which renders the same tab names as having no template at all.
+    fn default() -> Self {
+        Template {
+            source: String::from("{status}{name}"),
+            segments: vec![
+                Segment::Placeholder(Placeholder::Status),
+                Segment::Placeholder(Placeholder::Name),
+            ],
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, TemplateError> {
+        let mut chars = source.chars().peekable();
+        let segments = parse_segments(&mut chars, false)?;
+        Ok(Template {
+            source: source.to_string(),
+            segments,
+        })
+    }
+
+    /// The text the template was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn render(&self, value: &dyn Fn(Placeholder) -> String) -> String {
+        render_segments(&self.segments, value).0
+    }
+
+    /// Whether the template references `placeholder` anywhere.
+    pub fn uses(&self, placeholder: Placeholder) -> bool {
+        segments_use(&self.segments, placeholder)
+    }
+
+    /// Whether the template references any placeholder, as opposed to being plain text.
+    pub fn has_placeholders(&self) -> bool {
+        Placeholder::ALL
+            .iter()
+            .any(|placeholder| self.uses(*placeholder))
+    }
+}
+
+fn segments_use(segments: &[Segment], placeholder: Placeholder) -> bool {
+    segments.iter().any(|segment| match segment {
+        Segment::Literal(_) => false,
+        Segment::Placeholder(used) => *used == placeholder,
+        Segment::Optional(inner) => segments_use(inner, placeholder),
+    })
+}
+
+fn parse_segments(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    in_group: bool,
+) -> Result<Vec<Segment>, TemplateError> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' if in_group => {
+                flush_literal(&mut segments, &mut literal);
+                return Ok(segments);
+            }
+            '}' => return Err(TemplateError::UnexpectedClosingBrace),
+            '{' if chars.peek() == Some(&'?') => {
+                chars.next();
+                flush_literal(&mut segments, &mut literal);
+                segments.push(Segment::Optional(parse_segments(chars, true)?));
+            }
+            '{' => {
+                flush_literal(&mut segments, &mut literal);
+
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(TemplateError::UnclosedBrace),
+                    }
+                }
+
+                let name = name.trim();
+                let Some(placeholder) = Placeholder::from_name(name) else {
+                    return Err(TemplateError::UnknownPlaceholder(name.to_string()));
+                };
+                segments.push(Segment::Placeholder(placeholder));
+            }
+            c => literal.push(c),
+        }
+    }
+
+    if in_group {
+        return Err(TemplateError::UnclosedBrace);
+    }
+
+    flush_literal(&mut segments, &mut literal);
+    Ok(segments)
+}
+
+fn flush_literal(segments: &mut Vec<Segment>, literal: &mut String) {
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(std::mem::take(literal)));
+    }
+}
+
+/// Returns the rendered segments and whether any placeholder rendered to a non-empty value.
+fn render_segments(segments: &[Segment], value: &dyn Fn(Placeholder) -> String) -> (String, bool) {
+    let mut result = String::new();
+    let mut has_value = false;
+
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) => result.push_str(literal),
+            Segment::Placeholder(placeholder) => {
+                let rendered = value(*placeholder);
+                has_value |= !rendered.is_empty();
+                result.push_str(&rendered);
+            }
+            Segment::Optional(inner) => {
+                let (rendered, inner_has_value) = render_segments(inner, value);
+                if inner_has_value {
+                    has_value = true;
+                    result.push_str(&rendered);
+                }
+            }
+        }
+    }
+
+    (result, has_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(placeholder: Placeholder) -> String {
+        match placeholder {
+            Placeholder::Repo => "tabula".to_string(),
+            Placeholder::RelPath => "src".to_string(),
+            Placeholder::PaneCount => "2".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn renders_placeholders_and_literals() {
+        let template = Template::parse("{repo}/{rel_path} ({pane_count})").unwrap();
+
+        assert_eq!(template.render(&values), "tabula/src (2)");
+    }
+
+    #[test]
+    fn skips_optional_groups_without_values() {
+        let template = Template::parse("{repo}{?/{rel_path}}{? [{worktree}]}").unwrap();
+
+        assert_eq!(template.render(&values), "tabula/src");
+    }
+
+    #[test]
+    fn renders_escaped_braces() {
+        let template = Template::parse("{{{repo}}}").unwrap();
+
+        assert_eq!(template.render(&values), "{tabula}");
+    }
+
+    #[test]
+    fn tells_templates_from_plain_text() {
+        assert!(Template::parse("api: {auto}").unwrap().has_placeholders());
+        assert!(!Template::parse("api {{notes}}").unwrap().has_placeholders());
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert_eq!(
+            Template::parse("{branchh}"),
+            Err(TemplateError::UnknownPlaceholder("branchh".to_string()))
+        );
+        assert_eq!(Template::parse("{repo"), Err(TemplateError::UnclosedBrace));
+        assert_eq!(
+            Template::parse("{?{repo}"),
+            Err(TemplateError::UnclosedBrace)
+        );
+        assert_eq!(
+            Template::parse("repo}"),
+            Err(TemplateError::UnexpectedClosingBrace)
+        );
+    }
+}